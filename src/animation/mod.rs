@@ -0,0 +1,398 @@
+use color_quant::NeuQuant;
+use gif::{DisposalMethod, Encoder, Frame, Repeat};
+use image::{
+    DynamicImage, ExtendedColorType, GenericImageView, RgbaImage,
+    codecs::webp::WebPEncoder,
+    imageops::{self, FilterType},
+};
+use wasm_bindgen::{JsError, prelude::wasm_bindgen};
+
+use crate::{
+    chaining::ImageProcessor,
+    utils::{pixels::premultiplied, read_image::read_image},
+};
+
+/// How frames of a different size are fitted into the animation canvas.
+#[wasm_bindgen]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ResizeMode {
+    /// Scale the frame to fit inside the canvas, keeping the aspect ratio.
+    /// The uncovered area is left transparent.
+    #[default]
+    Fit,
+    /// Scale the frame to cover the whole canvas, keeping the aspect ratio.
+    /// Whatever overflows the canvas is cropped from the center.
+    Fill,
+    /// Scale the frame to the exact canvas size, ignoring the aspect ratio.
+    Stretch,
+}
+
+/// Builder for animated GIF and WebP images made from a sequence of stills.
+///
+/// ```javascript
+/// const gifBytes = new Animation(480, 320)
+///   .resize_mode(ResizeMode.Fill)
+///   .loop_count(0)
+///   .add_frame(firstBytes, 500)
+///   .add_frame(secondBytes, 500)
+///   .add_processor(new ImageProcessor(thirdBytes).grayscale(), 1000)
+///   .encode_gif();
+/// ```
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct Animation {
+    width: u32,
+    height: u32,
+    resize_mode: ResizeMode,
+    loop_count: u16,
+    frames: Vec<(DynamicImage, u32)>,
+}
+
+#[wasm_bindgen]
+impl Animation {
+    /// Create an empty animation with the given canvas size.
+    /// Passing 0 for both sides uses the size of the first frame.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            resize_mode: ResizeMode::default(),
+            loop_count: 0,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Set how frames are fitted into the canvas. Defaults to `ResizeMode.Fit`.
+    pub fn resize_mode(mut self, mode: ResizeMode) -> Self {
+        self.resize_mode = mode;
+        self
+    }
+
+    /// Set how many times the animation plays.
+    /// 0 (the default) loops forever.
+    pub fn loop_count(mut self, count: u16) -> Self {
+        self.loop_count = count;
+        self
+    }
+
+    /// Append a frame from raw image bytes.
+    /// `delay_ms` is how long the frame stays on screen in milliseconds.
+    pub fn add_frame(mut self, image_data: Vec<u8>, delay_ms: u32) -> Result<Self, JsError> {
+        let image = read_image(image_data)
+            .map_err(|err| JsError::new(&format!("Failed to read frame: {err}")))?;
+        self.frames.push((image, delay_ms));
+        Ok(self)
+    }
+
+    /// Append the output of an `ImageProcessor` chain as a frame.
+    pub fn add_processor(mut self, processor: ImageProcessor, delay_ms: u32) -> Self {
+        self.frames.push((processor.into_image(), delay_ms));
+        self
+    }
+
+    /// Encode the frames as an animated GIF.
+    /// All frames share a single global palette.
+    pub fn encode_gif(self) -> Result<Vec<u8>, JsError> {
+        let frames = self.normalize_frames()?;
+        let (width, height) = frames[0].0.dimensions();
+        let (width, height) = (
+            u16::try_from(width).map_err(|_| JsError::new("GIF width is limited to 65535"))?,
+            u16::try_from(height).map_err(|_| JsError::new("GIF height is limited to 65535"))?,
+        );
+
+        let palette = SharedPalette::new(&frames);
+
+        let mut buf = Vec::new();
+        let mut encoder = Encoder::new(&mut buf, width, height, &palette.color_map())
+            .map_err(|err| JsError::new(&format!("Failed to create the GIF: {err}")))?;
+
+        // GIF counts the extra plays after the first one.
+        let repeat = match self.loop_count {
+            0 => Some(Repeat::Infinite),
+            1 => None,
+            count => Some(Repeat::Finite(count - 1)),
+        };
+        if let Some(repeat) = repeat {
+            encoder
+                .set_repeat(repeat)
+                .map_err(|err| JsError::new(&format!("Failed to set the loop count: {err}")))?;
+        }
+
+        for (image, delay_ms) in &frames {
+            let mut frame = Frame::from_indexed_pixels(
+                width,
+                height,
+                palette.index(image),
+                palette.transparent,
+            );
+            frame.delay = (delay_ms.div_ceil(10)).min(u16::MAX as u32) as u16;
+            frame.dispose = DisposalMethod::Background;
+            encoder
+                .write_frame(&frame)
+                .map_err(|err| JsError::new(&format!("Failed to write GIF frame: {err}")))?;
+        }
+
+        drop(encoder);
+        Ok(buf)
+    }
+
+    /// Encode the frames as an animated, lossless WebP.
+    pub fn encode_webp(self) -> Result<Vec<u8>, JsError> {
+        let frames = self.normalize_frames()?;
+        let (width, height) = frames[0].0.dimensions();
+        if width > 1 << 24 || height > 1 << 24 {
+            return Err(JsError::new("WebP sides are limited to 16777216 pixels"));
+        }
+
+        let has_alpha = frames
+            .iter()
+            .any(|(image, _)| image.pixels().any(|pixel| pixel[3] < u8::MAX));
+
+        let mut body = Vec::new();
+
+        let mut vp8x = Vec::with_capacity(10);
+        vp8x.push(0b0000_0010 | if has_alpha { 0b0001_0000 } else { 0 });
+        vp8x.extend_from_slice(&[0; 3]);
+        vp8x.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+        vp8x.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+        write_chunk(&mut body, b"VP8X", &vp8x);
+
+        let mut anim = Vec::with_capacity(6);
+        anim.extend_from_slice(&[0; 4]);
+        anim.extend_from_slice(&self.loop_count.to_le_bytes());
+        write_chunk(&mut body, b"ANIM", &anim);
+
+        for (image, delay_ms) in &frames {
+            let mut still = Vec::new();
+            WebPEncoder::new_lossless(&mut still)
+                .encode(image.as_raw(), width, height, ExtendedColorType::Rgba8)
+                .map_err(|err| JsError::new(&format!("Failed to encode WebP frame: {err}")))?;
+
+            let vp8l = vp8l_payload(&still).ok_or_else(|| {
+                JsError::new("Failed to encode WebP frame: unexpected still image layout")
+            })?;
+
+            let mut anmf = Vec::with_capacity(16 + 8 + vp8l.len());
+            anmf.extend_from_slice(&[0; 6]);
+            anmf.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+            anmf.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+            anmf.extend_from_slice(&(*delay_ms).min((1 << 24) - 1).to_le_bytes()[..3]);
+            // Do not blend with the previous frame, keep it on screen until replaced.
+            anmf.push(0b0000_0010);
+            write_chunk(&mut anmf, b"VP8L", vp8l);
+            write_chunk(&mut body, b"ANMF", &anmf);
+        }
+
+        let mut buf = Vec::with_capacity(body.len() + 12);
+        buf.extend_from_slice(b"RIFF");
+        buf.extend_from_slice(&(body.len() as u32 + 4).to_le_bytes());
+        buf.extend_from_slice(b"WEBP");
+        buf.extend_from_slice(&body);
+
+        Ok(buf)
+    }
+}
+
+impl Animation {
+    /// Bring every frame to the canvas size using the chosen resize mode.
+    fn normalize_frames(&self) -> Result<Vec<(RgbaImage, u32)>, JsError> {
+        let Some((first, _)) = self.frames.first() else {
+            return Err(JsError::new("An animation needs at least one frame"));
+        };
+
+        let (width, height) = if self.width == 0 && self.height == 0 {
+            first.dimensions()
+        } else {
+            (self.width, self.height)
+        };
+        if width == 0 || height == 0 {
+            return Err(JsError::new("The animation size must not be 0"));
+        }
+
+        let frames = self
+            .frames
+            .iter()
+            .map(|(image, delay_ms)| (fit_frame(image, width, height, self.resize_mode), *delay_ms))
+            .collect();
+
+        Ok(frames)
+    }
+}
+
+fn fit_frame(image: &DynamicImage, width: u32, height: u32, mode: ResizeMode) -> RgbaImage {
+    if image.dimensions() == (width, height) {
+        return image.to_rgba8();
+    }
+
+    let resize = |resize: fn(&DynamicImage, u32, u32, FilterType) -> DynamicImage| {
+        premultiplied(image, |image| {
+            resize(image, width, height, FilterType::Triangle)
+        })
+        .to_rgba8()
+    };
+
+    match mode {
+        ResizeMode::Stretch => resize(DynamicImage::resize_exact),
+        ResizeMode::Fill => resize(DynamicImage::resize_to_fill),
+        ResizeMode::Fit => {
+            let resized = resize(DynamicImage::resize);
+            let mut canvas = RgbaImage::new(width, height);
+            let x = (width - resized.width()) / 2;
+            let y = (height - resized.height()) / 2;
+            imageops::replace(&mut canvas, &resized, x.into(), y.into());
+            canvas
+        }
+    }
+}
+
+/// A single palette computed from the pixels of every frame.
+struct SharedPalette {
+    quant: NeuQuant,
+    transparent: Option<u8>,
+}
+
+impl SharedPalette {
+    /// Upper bound of pixels fed to the quantizer, spread over all frames.
+    const SAMPLE_BUDGET: usize = 1 << 20;
+
+    fn new(frames: &[(RgbaImage, u32)]) -> Self {
+        let total: usize = frames
+            .iter()
+            .map(|(image, _)| image.as_raw().len() / 4)
+            .sum();
+        let step = total.div_ceil(Self::SAMPLE_BUDGET).max(1);
+
+        let mut transparent = false;
+        let mut samples = Vec::with_capacity(total.div_ceil(step) * 4);
+        for (image, _) in frames {
+            for pixel in image.pixels().step_by(step) {
+                if pixel[3] < 128 {
+                    transparent = true;
+                } else {
+                    samples.extend_from_slice(&[pixel[0], pixel[1], pixel[2], u8::MAX]);
+                }
+            }
+        }
+        if samples.is_empty() {
+            samples.extend_from_slice(&[0, 0, 0, u8::MAX]);
+        }
+
+        // Keep the last palette slot free for the transparent color.
+        let colors = if transparent { 255 } else { 256 };
+        Self {
+            quant: NeuQuant::new(10, colors, &samples),
+            transparent: transparent.then_some(255),
+        }
+    }
+
+    fn color_map(&self) -> Vec<u8> {
+        let mut palette = self.quant.color_map_rgb();
+        if self.transparent.is_some() {
+            palette.extend_from_slice(&[0, 0, 0]);
+        }
+        palette
+    }
+
+    fn index(&self, image: &RgbaImage) -> Vec<u8> {
+        image
+            .pixels()
+            .map(|pixel| match self.transparent {
+                Some(index) if pixel[3] < 128 => index,
+                _ => self
+                    .quant
+                    .index_of(&[pixel[0], pixel[1], pixel[2], u8::MAX]) as u8,
+            })
+            .collect()
+    }
+}
+
+/// The image data of a lossless WebP still laid out as
+/// "RIFF" <size> "WEBP" "VP8L" <size> <data>, or `None` for any other layout.
+fn vp8l_payload(still: &[u8]) -> Option<&[u8]> {
+    if still.get(..4)? != b"RIFF" || still.get(8..12)? != b"WEBP" || still.get(12..16)? != b"VP8L" {
+        return None;
+    }
+    let len = u32::from_le_bytes(still.get(16..20)?.try_into().ok()?) as usize;
+    still.get(20..20usize.checked_add(len)?)
+}
+
+fn write_chunk(buf: &mut Vec<u8>, name: &[u8; 4], data: &[u8]) {
+    buf.extend_from_slice(name);
+    buf.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    buf.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        buf.push(0);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use image::{AnimationDecoder, codecs::gif::GifDecoder, codecs::webp::WebPDecoder};
+
+    use super::*;
+
+    fn sample_frames() -> Animation {
+        let test_image_data = include_bytes!("../../sample.jpg").to_vec();
+        let processor = ImageProcessor::new(test_image_data.clone())
+            .unwrap()
+            .grayscale()
+            .unwrap();
+
+        Animation::new(320, 240)
+            .resize_mode(ResizeMode::Fill)
+            .add_frame(test_image_data.clone(), 200)
+            .unwrap()
+            .add_processor(processor, 200)
+    }
+
+    #[test]
+    fn test_encode_gif() {
+        let bytes = sample_frames().encode_gif().unwrap();
+
+        let decoder = GifDecoder::new(Cursor::new(&bytes)).unwrap();
+        let frames = decoder.into_frames().collect_frames().unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].buffer().dimensions(), (320, 240));
+        assert_eq!(frames[1].delay().numer_denom_ms(), (200, 1));
+
+        std::fs::write("test-output/animation.gif", bytes).unwrap();
+    }
+
+    #[test]
+    fn test_encode_webp() {
+        let bytes = sample_frames().loop_count(3).encode_webp().unwrap();
+
+        let decoder = WebPDecoder::new(Cursor::new(&bytes)).unwrap();
+        let frames = decoder.into_frames().collect_frames().unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].buffer().dimensions(), (320, 240));
+
+        std::fs::write("test-output/animation.webp", bytes).unwrap();
+    }
+
+    #[test]
+    fn test_vp8l_payload_checks_layout() {
+        let mut still = b"RIFF\x10\0\0\0WEBPVP8L\x04\0\0\0data".to_vec();
+        assert_eq!(vp8l_payload(&still), Some(&b"data"[..]));
+
+        still[16] = 5;
+        assert_eq!(vp8l_payload(&still), None);
+        still[16] = 4;
+        still[12..16].copy_from_slice(b"VP8X");
+        assert_eq!(vp8l_payload(&still), None);
+        assert_eq!(vp8l_payload(b"RIFF"), None);
+    }
+
+    #[test]
+    fn test_fit_keeps_transparent_borders() {
+        let wide =
+            DynamicImage::ImageRgba8(RgbaImage::from_pixel(40, 10, image::Rgba([255, 0, 0, 255])));
+        let framed = fit_frame(&wide, 20, 20, ResizeMode::Fit);
+
+        assert_eq!(framed.dimensions(), (20, 20));
+        assert_eq!(framed.get_pixel(10, 0)[3], 0);
+        assert_eq!(*framed.get_pixel(10, 10), image::Rgba([255, 0, 0, 255]));
+    }
+}
@@ -1,17 +1,41 @@
-use wasm_bindgen::{JsError, JsValue, prelude::wasm_bindgen};
+use image::{ColorType, DynamicImage, ImageFormat, RgbaImage};
+use wasm_bindgen::{JsError, prelude::wasm_bindgen};
 
 use crate::color_filters::{
-    blur::blur, brighten::brighten, contrast::contrast, fast_blur::fast_blur, grayscale::grayscale,
-    hue_rotate::hue_rotate, invert::invert,
-};
-use crate::transformation::{
-    crop::crop, resize::resize, resize_square::resize_square, thumbnail::thumbnail,
+    auto_levels, bit_depth, blur,
+    color_matrix::ColorMatrix,
+    convolve::{Convolution, EdgeMode},
+    css_filter,
+    curves::Curves,
+    denoise,
+    dither::Dither,
+    drop_shadow,
+    edge_detection::{self, EdgeOperator},
+    equalize, fast_blur, grain,
+    hsl::{self, HueRange},
+    levels::Levels,
+    lut::Lut,
+    pixelate,
+    quantize::{self, Quantizer},
+    redact, sharpen, threshold, tone, vignette,
+    white_balance::{self, WhiteBalance},
 };
+use crate::memory::WasmBuffer;
+use crate::presets;
+use crate::region::Region;
+use crate::transformation::{crop, quality::ResizeQuality, resize, resize_square, thumbnail};
+use crate::utils::{read_image::read_image, write_image::write_image};
+
+mod point_ops;
+pub mod rgba_pixels;
+
+use point_ops::PointOps;
+use rgba_pixels::RgbaPixels;
 
 // This class is here to be used in a builder pattern.
 // It allows for a single image to go through multiplce modifications
 #[wasm_bindgen]
-#[derive(Debug, Default)]
+#[derive(Debug)]
 /// Builder-style image processor for JS/Wasm usage.
 ///
 /// ```javascript
@@ -61,104 +85,595 @@ use crate::transformation::{
 ///   ctx.drawImage(bitmap, 0, 0);
 /// });
 /// ```
+///
+/// ```javascript
+/// // Canvas pixels in and out, without encoding to PNG in between
+/// const ctx = canvas.getContext("2d");
+/// const { width, height, data } = ctx.getImageData(0, 0, canvas.width, canvas.height);
+///
+/// const pixels = ImageProcessor.from_rgba(width, height, data)
+///   .brighten(20)
+///   .hue_rotate(90)
+///   .to_rgba();
+///
+/// ctx.putImageData(new ImageData(pixels.data, pixels.width, pixels.height), 0, 0);
+/// ```
 pub struct ImageProcessor {
-    image: Vec<u8>,
-}
-
-fn to_js_error(context: &str, err: JsValue) -> JsError {
-    let message = err
-        .as_string()
-        .unwrap_or_else(|| "Unknown error".to_string());
-    JsError::new(&format!("{context}: {message}"))
+    image: DynamicImage,
+    format: ImageFormat,
+    region: Option<Region>,
+    auto_sharpen: bool,
+    quality: ResizeQuality,
+    linear_light: bool,
+    indexed_png: bool,
+    point_ops: PointOps,
 }
 
 #[wasm_bindgen]
 impl ImageProcessor {
     /// Create a new processor from raw image bytes.
-    pub fn new(image: Vec<u8>) -> Self {
-        Self { image }
+    /// The image is decoded once here and encoded again by `process`.
+    pub fn new(image: Vec<u8>) -> Result<Self, JsError> {
+        let format = image::guess_format(&image)
+            .map_err(|err| JsError::new(&format!("Failed to get image type: {err}")))?;
+
+        let image = read_image(image)
+            .map_err(|err| JsError::new(&format!("Failed to read image: {err}")))?;
+
+        Ok(Self {
+            image,
+            format,
+            region: None,
+            auto_sharpen: false,
+            quality: ResizeQuality::default(),
+            linear_light: false,
+            indexed_png: false,
+            point_ops: PointOps::default(),
+        })
+    }
+
+    /// Create a new processor from raw RGBA pixels, for example the `data`
+    /// of a canvas `ImageData`. `process` encodes these images as PNG.
+    pub fn from_rgba(width: u32, height: u32, data: Vec<u8>) -> Result<Self, JsError> {
+        let image = RgbaImage::from_raw(width, height, data).ok_or_else(|| {
+            JsError::new(&format!(
+                "Expected {} bytes of RGBA data for a {width}x{height} image",
+                width as usize * height as usize * 4
+            ))
+        })?;
+
+        Ok(Self {
+            image: DynamicImage::ImageRgba8(image),
+            format: ImageFormat::Png,
+            region: None,
+            auto_sharpen: false,
+            quality: ResizeQuality::default(),
+            linear_light: false,
+            indexed_png: false,
+            point_ops: PointOps::default(),
+        })
+    }
+
+    /// Create a new processor from image bytes JS wrote into a `WasmBuffer`.
+    /// The bytes are decoded in place, the buffer can be freed afterwards.
+    pub fn from_buffer(buffer: &WasmBuffer) -> Result<Self, JsError> {
+        let format = image::guess_format(buffer.as_slice())
+            .map_err(|err| JsError::new(&format!("Failed to get image type: {err}")))?;
+
+        let image = read_image(buffer.as_slice())
+            .map_err(|err| JsError::new(&format!("Failed to read image: {err}")))?;
+
+        Ok(Self {
+            image,
+            format,
+            region: None,
+            auto_sharpen: false,
+            quality: ResizeQuality::default(),
+            linear_light: false,
+            indexed_png: false,
+            point_ops: PointOps::default(),
+        })
+    }
+
+    /// Create a new processor from RGBA pixels JS wrote into a `WasmBuffer`.
+    /// The buffer becomes the image, so its memory is reused instead of copied.
+    pub fn from_rgba_buffer(width: u32, height: u32, buffer: WasmBuffer) -> Result<Self, JsError> {
+        Self::from_rgba(width, height, buffer.into_vec())
     }
 
     /// Calling this returns the final image bytes.
-    pub fn process(self) -> Vec<u8> {
-        self.image
+    pub fn process(self) -> Result<Vec<u8>, JsError> {
+        let processor = self.flush();
+        if processor.indexed_png && processor.format == ImageFormat::Png {
+            return quantize::write_indexed_png(&processor.image)
+                .map_err(|err| JsError::new(&format!("Failed to encode the image: {err}")));
+        }
+        write_image(&processor.image, processor.format)
+            .map_err(|err| JsError::new(&format!("Failed to encode the image: {err}")))
     }
 
-    pub fn resize(mut self, width: u32, height: u32) -> Result<Self, JsError> {
-        let buff = resize(self.image, width, height)
-            .map_err(|err| to_js_error("Failed to resize the image", err))?;
-        self.image = buff;
-        Ok(self)
+    /// Like `process`, but the bytes stay in wasm memory.
+    /// Read them through a view and `free()` the buffer when done.
+    pub fn process_to_buffer(self) -> Result<WasmBuffer, JsError> {
+        self.process().map(WasmBuffer::from)
     }
 
-    pub fn resize_square(mut self, side: u32) -> Result<Self, JsError> {
-        let buff = resize_square(self.image, side)
-            .map_err(|err| to_js_error("Failed to resize the image", err))?;
-        self.image = buff;
-        Ok(self)
+    /// Calling this returns the final image as raw RGBA pixels,
+    /// ready to be wrapped in a canvas `ImageData`.
+    pub fn to_rgba(self) -> RgbaPixels {
+        RgbaPixels::from(self.into_image().into_rgba8())
     }
 
-    pub fn thumbnail(mut self, width: u32, height: u32) -> Result<Self, JsError> {
-        let buff = thumbnail(self.image, width, height)
-            .map_err(|err| to_js_error("Failed to create the thumbnail", err))?;
-        self.image = buff;
-        Ok(self)
+    /// Like `to_rgba`, but the pixels stay in wasm memory.
+    /// RGBA images are handed over without another copy.
+    pub fn to_rgba_buffer(self) -> WasmBuffer {
+        WasmBuffer::from(self.into_image().into_rgba8().into_raw())
     }
 
-    pub fn crop(mut self, x: u32, y: u32, width: u32, height: u32) -> Result<Self, JsError> {
-        let buff = crop(self.image, x, y, width, height)
-            .map_err(|err| to_js_error("Failed to crop the image", err))?;
-        self.image = buff;
-        Ok(self)
+    pub fn resize(self, width: u32, height: u32) -> Result<Self, JsError> {
+        let quality = self.quality;
+        Ok(self.resample(|image| resize::apply_with(image, width, height, quality)))
     }
 
-    pub fn blur(mut self, sigma: f32) -> Result<Self, JsError> {
-        let buff =
-            blur(self.image, sigma).map_err(|err| to_js_error("Failed to blur the image", err))?;
-        self.image = buff;
-        Ok(self)
+    pub fn resize_square(self, side: u32) -> Result<Self, JsError> {
+        let quality = self.quality;
+        Ok(self.resample(|image| resize_square::apply_with(image, side, quality)))
     }
 
-    pub fn fast_blur(mut self, sigma: f32) -> Result<Self, JsError> {
-        let buff = fast_blur(self.image, sigma)
-            .map_err(|err| to_js_error("Failed to blur the image", err))?;
-        self.image = buff;
-        Ok(self)
+    pub fn thumbnail(self, width: u32, height: u32) -> Result<Self, JsError> {
+        let quality = self.quality;
+        Ok(self.resample(|image| thumbnail::apply_with(image, width, height, quality)))
     }
 
-    pub fn brighten(mut self, value: i32) -> Result<Self, JsError> {
-        let buff = brighten(self.image, value)
-            .map_err(|err| to_js_error("Failed to adjust brightness", err))?;
-        self.image = buff;
-        Ok(self)
+    pub fn crop(self, x: u32, y: u32, width: u32, height: u32) -> Result<Self, JsError> {
+        Ok(self.transform(|image| crop::apply(image, x, y, width, height)))
     }
 
-    pub fn contrast(mut self, value: f32) -> Result<Self, JsError> {
-        let buff = contrast(self.image, value)
-            .map_err(|err| to_js_error("Failed to adjust contrast", err))?;
-        self.image = buff;
-        Ok(self)
+    /// Apply a mild unsharp mask after every resize, square resize or thumbnail
+    /// that follows and shrinks both sides, so resized photos do not look soft.
+    /// Crops are left alone. Off by default.
+    pub fn auto_sharpen(mut self, enabled: bool) -> Self {
+        self.auto_sharpen = enabled;
+        self
+    }
+
+    /// How the resizes that follow resample the image. `Balanced` and `Best`
+    /// resize in linear light, blurs are left to `linear_light`. `Fast` by default.
+    pub fn resize_quality(mut self, quality: ResizeQuality) -> Self {
+        self.quality = quality;
+        self
+    }
+
+    /// Blur in linear light from here on, which keeps bright details from
+    /// turning muddy. Off by default.
+    pub fn linear_light(mut self, enabled: bool) -> Self {
+        self.linear_light = enabled;
+        self
+    }
+
+    /// Reduce the image to 8 bits per channel, see `to_8bit`.
+    /// Operations keep the bit depth of the image, so this is the only place
+    /// 16-bit and float images lose precision before encoding.
+    pub fn to_8bit(self, dither: bool) -> Result<Self, JsError> {
+        let mut processor = self.flush();
+        processor.image = bit_depth::apply(&processor.image, ColorType::L8, dither);
+        Ok(processor)
+    }
+
+    /// Convert the image to 16 bits per channel, see `to_16bit`.
+    pub fn to_16bit(self, dither: bool) -> Result<Self, JsError> {
+        let mut processor = self.flush();
+        processor.image = bit_depth::apply(&processor.image, ColorType::L16, dither);
+        Ok(processor)
+    }
+
+    /// Encode PNG output as an indexed color file, with a `tRNS` chunk for
+    /// transparency. Images with more than 256 colors are quantized on the way
+    /// out; use `quantize` first to pick fewer colors or dither. Off by default.
+    pub fn indexed_png(mut self, enabled: bool) -> Self {
+        self.indexed_png = enabled;
+        self
+    }
+
+    /// Restrict the filters that follow to a part of the image.
+    /// Transformations like `resize` and `crop` still act on the whole image.
+    pub fn region(self, region: Region) -> Self {
+        let mut processor = self.flush();
+        processor.region = Some(region);
+        processor
+    }
+
+    /// Let the filters that follow act on the whole image again.
+    pub fn clear_region(self) -> Self {
+        let mut processor = self.flush();
+        processor.region = None;
+        processor
+    }
+
+    pub fn blur(self, sigma: f32) -> Result<Self, JsError> {
+        if self.linear_light {
+            return Ok(self.filter(|image| blur::apply_linear(image, sigma)));
+        }
+        Ok(self.filter(|image| blur::apply(image, sigma)))
+    }
+
+    pub fn fast_blur(self, sigma: f32) -> Result<Self, JsError> {
+        if self.linear_light {
+            return Ok(self.filter(|image| fast_blur::apply_linear(image, sigma)));
+        }
+        Ok(self.filter(|image| fast_blur::apply(image, sigma)))
+    }
+
+    pub fn brighten(self, value: i32) -> Result<Self, JsError> {
+        Ok(self.point(ColorMatrix::brighten(value)))
+    }
+
+    pub fn contrast(self, value: f32) -> Result<Self, JsError> {
+        Ok(self.point(ColorMatrix::contrast(value)))
     }
 
     pub fn grayscale(mut self) -> Result<Self, JsError> {
-        let buff = grayscale(self.image)
-            .map_err(|err| to_js_error("Failed to convert to grayscale", err))?;
-        self.image = buff;
+        self.point_ops.push_grayscale();
         Ok(self)
     }
 
-    pub fn invert(mut self) -> Result<Self, JsError> {
-        let buff =
-            invert(self.image).map_err(|err| to_js_error("Failed to invert the image", err))?;
-        self.image = buff;
-        Ok(self)
+    /// Black and white at a fixed level (0-255), see `threshold`.
+    /// Pair with `indexed_png(true)` for 1-bit PNG output.
+    pub fn threshold(self, value: f32) -> Result<Self, JsError> {
+        Ok(self.filter(|image| threshold::apply(image, value)))
     }
 
-    pub fn hue_rotate(mut self, degrees: i32) -> Result<Self, JsError> {
-        let buff = hue_rotate(self.image, degrees)
-            .map_err(|err| to_js_error("Failed to hue rotate", err))?;
-        self.image = buff;
-        Ok(self)
+    /// Black and white at the level Otsu's method picks, see `otsu_threshold`.
+    pub fn otsu_threshold(self) -> Result<Self, JsError> {
+        Ok(self.filter(threshold::apply_otsu))
+    }
+
+    /// Black and white against the local mean, see `adaptive_threshold`.
+    pub fn adaptive_threshold(self, window_size: u32, offset: f32) -> Result<Self, JsError> {
+        Ok(self.filter(|image| threshold::apply_adaptive(image, window_size, offset)))
+    }
+
+    /// Black and white with Sauvola's local threshold, see `sauvola_threshold`.
+    pub fn sauvola_threshold(self, window_size: u32, k: f32) -> Result<Self, JsError> {
+        Ok(self.filter(|image| threshold::apply_sauvola(image, window_size, k)))
+    }
+
+    pub fn invert(self) -> Result<Self, JsError> {
+        Ok(self.point(ColorMatrix::invert()))
+    }
+
+    pub fn sepia(self, amount: f32) -> Result<Self, JsError> {
+        Ok(self.point(ColorMatrix::sepia(amount)))
+    }
+
+    pub fn saturate(self, amount: f32) -> Result<Self, JsError> {
+        Ok(self.point(ColorMatrix::saturate(amount)))
+    }
+
+    pub fn opacity(self, amount: f32) -> Result<Self, JsError> {
+        Ok(self.point(ColorMatrix::opacity(amount)))
+    }
+
+    /// Draw a shadow behind the opaque parts, see `drop_shadow` for the arguments.
+    pub fn drop_shadow(
+        self,
+        offset_x: f32,
+        offset_y: f32,
+        blur_radius: f32,
+        color: &str,
+    ) -> Result<Self, JsError> {
+        let color = drop_shadow::parse_color(color)
+            .ok_or_else(|| JsError::new(&format!("Failed to parse color: {color}")))?;
+        Ok(self.filter(|image| drop_shadow::apply(image, offset_x, offset_y, blur_radius, color)))
+    }
+
+    /// Apply a CSS `filter` value like `"sepia(60%) blur(2px)"`.
+    pub fn css_filter(self, filter: &str) -> Result<Self, JsError> {
+        let functions = css_filter::parse(filter).map_err(|err| JsError::new(&err))?;
+        Ok(self.filter(|image| css_filter::apply(image.clone(), &functions)))
+    }
+
+    /// Replace the image with a grayscale map of its edges.
+    pub fn detect_edges(self, operator: EdgeOperator) -> Result<Self, JsError> {
+        Ok(self.filter(|image| edge_detection::apply(image, operator)))
+    }
+
+    /// Replace the image with its Canny edges, see `canny` for the thresholds.
+    pub fn canny(self, low: f32, high: f32) -> Result<Self, JsError> {
+        Ok(self.filter(|image| edge_detection::apply_canny_image(image, low, high)))
+    }
+
+    pub fn hue_rotate(self, degrees: i32) -> Result<Self, JsError> {
+        Ok(self.point(ColorMatrix::hue_rotate(degrees as f32)))
+    }
+
+    pub fn sharpen(self, strength: f32) -> Result<Self, JsError> {
+        Ok(self.filter(|image| sharpen::apply_sharpen(image, strength)))
+    }
+
+    pub fn unsharpen(self, sigma: f32, amount: f32, threshold: i32) -> Result<Self, JsError> {
+        Ok(self.filter(|image| sharpen::apply_unsharpen(image, sigma, amount, threshold)))
+    }
+
+    pub fn median(self, radius: u32) -> Result<Self, JsError> {
+        Ok(self.filter(|image| denoise::apply_median(image, radius)))
+    }
+
+    pub fn bilateral(self, sigma_spatial: f32, sigma_range: f32) -> Result<Self, JsError> {
+        Ok(self.filter(|image| denoise::apply_bilateral(image, sigma_spatial, sigma_range)))
+    }
+
+    pub fn pixelate(self, block_size: u32) -> Result<Self, JsError> {
+        if block_size == 0 {
+            return Err(JsError::new("The block size must be at least 1"));
+        }
+        Ok(self.filter(|image| pixelate::apply(image, block_size)))
+    }
+
+    /// Transform every pixel with a 4x5 color matrix, see `color_matrix`.
+    pub fn color_matrix(self, matrix: Vec<f32>) -> Result<Self, JsError> {
+        let matrix = ColorMatrix::from_slice(&matrix).map_err(|err| JsError::new(&err))?;
+        Ok(self.point(matrix))
+    }
+
+    /// Color grade with a `.cube` or HaldCLUT LUT, see `apply_lut`.
+    pub fn apply_lut(self, lut_bytes: Vec<u8>, intensity: f32) -> Result<Self, JsError> {
+        let lut = Lut::parse(&lut_bytes).map_err(|err| JsError::new(&err))?;
+        Ok(self.filter(|image| lut.apply(image, intensity)))
+    }
+
+    /// Stretch tones between input points, see `levels`.
+    pub fn levels(
+        self,
+        black: f32,
+        white: f32,
+        gamma: f32,
+        out_black: f32,
+        out_white: f32,
+    ) -> Result<Self, JsError> {
+        let levels = Levels::new(black, white, gamma, out_black, out_white)
+            .map_err(|err| JsError::new(&err))?;
+        Ok(self.filter(|image| levels.apply(image)))
+    }
+
+    /// Change the exposure by `ev` stops, see `exposure`.
+    pub fn exposure(self, ev: f32) -> Result<Self, JsError> {
+        Ok(self.filter(|image| tone::apply_exposure(image, ev)))
+    }
+
+    /// Lift or deepen the dark areas, see `shadows`.
+    pub fn shadows(self, amount: f32) -> Result<Self, JsError> {
+        Ok(self.filter(|image| tone::apply_shadows_highlights(image, amount, 0.0)))
+    }
+
+    /// Recover or brighten the bright areas, see `highlights`.
+    pub fn highlights(self, amount: f32) -> Result<Self, JsError> {
+        Ok(self.filter(|image| tone::apply_shadows_highlights(image, 0.0, amount)))
+    }
+
+    /// Warm (positive) or cool (negative) the colors, see `temperature`.
+    pub fn temperature(self, kelvin_shift: f32) -> Result<Self, JsError> {
+        Ok(self.point(white_balance::temperature_matrix(kelvin_shift)))
+    }
+
+    /// Shift the colors between green and magenta, see `tint`.
+    pub fn tint(self, value: f32) -> Result<Self, JsError> {
+        Ok(self.point(white_balance::tint_matrix(value)))
+    }
+
+    /// Remove the color cast of the light, see `auto_white_balance`.
+    /// The light is measured on the whole image, even with a region set.
+    pub fn auto_white_balance(self, method: WhiteBalance) -> Result<Self, JsError> {
+        Ok(self.filter(|image| white_balance::auto_matrix(image, method).apply(image)))
+    }
+
+    /// White balance so the pixel at `x`, `y` becomes gray, see `white_balance_at`.
+    pub fn white_balance_at(self, x: u32, y: u32) -> Result<Self, JsError> {
+        let processor = self.flush();
+        let matrix = white_balance::neutral_point_matrix(&processor.image, x, y)
+            .map_err(|err| JsError::new(&err))?;
+        Ok(processor.point(matrix))
+    }
+
+    /// Change how colorful the image is in OKLCH, see `saturation`.
+    pub fn saturation(self, amount: f32) -> Result<Self, JsError> {
+        Ok(self.filter(|image| hsl::apply_saturation(image, amount)))
+    }
+
+    /// Boost dull colors more than vivid ones, see `vibrance`.
+    pub fn vibrance(self, amount: f32) -> Result<Self, JsError> {
+        Ok(self.filter(|image| hsl::apply_vibrance(image, amount)))
+    }
+
+    /// Adjust the colors of one hue range, see `adjust_hue_range`.
+    pub fn adjust_hue_range(
+        self,
+        range: HueRange,
+        hue_shift: f32,
+        saturation: f32,
+        lightness: f32,
+    ) -> Result<Self, JsError> {
+        Ok(self
+            .filter(|image| hsl::apply_hue_range(image, range, hue_shift, saturation, lightness)))
+    }
+
+    /// Stretch tones to the full range automatically, see `auto_levels`.
+    pub fn auto_levels(
+        self,
+        clip_low: f32,
+        clip_high: f32,
+        per_channel: bool,
+    ) -> Result<Self, JsError> {
+        Ok(self.filter(|image| auto_levels::apply(image, clip_low, clip_high, per_channel)))
+    }
+
+    /// Stretch tones keeping the color balance, see `auto_contrast`.
+    pub fn auto_contrast(self, clip_low: f32, clip_high: f32) -> Result<Self, JsError> {
+        Ok(self.filter(|image| auto_levels::apply(image, clip_low, clip_high, false)))
+    }
+
+    /// Spread the brightness evenly, see `equalize`.
+    pub fn equalize(self) -> Result<Self, JsError> {
+        Ok(self.filter(equalize::apply_equalize))
+    }
+
+    /// Equalize tile by tile with limited contrast, see `clahe`.
+    pub fn clahe(self, tile_size: u32, clip_limit: f32) -> Result<Self, JsError> {
+        if tile_size == 0 {
+            return Err(JsError::new("The tile size must be at least 1"));
+        }
+        Ok(self.filter(|image| equalize::apply_clahe(image, tile_size, clip_limit)))
+    }
+
+    /// Remap tones with smooth curves, see `curves`.
+    pub fn curves(
+        self,
+        master: Vec<f32>,
+        red: Vec<f32>,
+        green: Vec<f32>,
+        blue: Vec<f32>,
+    ) -> Result<Self, JsError> {
+        let curves = Curves::new(&master, &red, &green, &blue).map_err(|err| JsError::new(&err))?;
+        Ok(self.filter(|image| curves.apply(image)))
+    }
+
+    /// Darken the corners, see `vignette`.
+    pub fn vignette(self, strength: f32) -> Result<Self, JsError> {
+        Ok(self.filter(|image| vignette::apply(image, strength)))
+    }
+
+    /// Add film grain, see `grain`.
+    pub fn grain(self, amount: f32) -> Result<Self, JsError> {
+        Ok(self.filter(|image| grain::apply(image, amount)))
+    }
+
+    /// Reduce the image to at most `colors` colors, see `quantize`.
+    pub fn quantize(self, colors: u32, dither: bool) -> Result<Self, JsError> {
+        let method = if dither {
+            Dither::FloydSteinberg
+        } else {
+            Dither::None
+        };
+        let quantizer = Quantizer::new(colors, method).map_err(|err| JsError::new(&err))?;
+        Ok(self.filter(|image| quantizer.apply(image)))
+    }
+
+    /// Reduce the image to `colors` computed colors mixed with `method`, see `dither`.
+    pub fn dither(self, method: Dither, colors: u32) -> Result<Self, JsError> {
+        let quantizer = Quantizer::new(colors, method).map_err(|err| JsError::new(&err))?;
+        Ok(self.filter(|image| quantizer.apply(image)))
+    }
+
+    /// Reduce the image to a fixed palette of RGB triples, see `dither_to_palette`.
+    pub fn dither_to_palette(self, method: Dither, palette: Vec<u8>) -> Result<Self, JsError> {
+        let quantizer = Quantizer::fixed(&palette, method).map_err(|err| JsError::new(&err))?;
+        Ok(self.filter(|image| quantizer.apply(image)))
+    }
+
+    /// Apply a named look, see `apply_preset`.
+    pub fn apply_preset(self, name: &str, intensity: f32) -> Result<Self, JsError> {
+        let steps = presets::steps(name).map_err(|err| JsError::new(&err))?;
+        Ok(self.filter(|image| presets::apply(image, &steps, intensity)))
+    }
+
+    /// Convolve with a custom odd sized kernel, see `convolve` for the arguments.
+    pub fn convolve(
+        self,
+        kernel: Vec<f32>,
+        divisor: f32,
+        bias: f32,
+        edge_mode: EdgeMode,
+        channels: &str,
+    ) -> Result<Self, JsError> {
+        let convolution = Convolution::new(kernel, divisor, bias, edge_mode, channels)
+            .map_err(|err| JsError::new(&err))?;
+        Ok(self.filter(|image| convolution.apply(image)))
+    }
+
+    /// Cover a rectangle with a solid color.
+    /// The box is exact and ignores the current region and its feathering.
+    #[allow(clippy::too_many_arguments)]
+    pub fn redact(
+        self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        r: u8,
+        g: u8,
+        b: u8,
+    ) -> Result<Self, JsError> {
+        let mut processor = self.flush();
+        processor.image = redact::apply(processor.image, x, y, width, height, [r, g, b]);
+        Ok(processor)
+    }
+}
+
+impl ImageProcessor {
+    /// Hand over the decoded image, for consumers inside this crate.
+    pub(crate) fn into_image(self) -> DynamicImage {
+        self.flush().image
+    }
+
+    /// Queue a point-wise color operation, to be applied together with the
+    /// ones around it before anything else reads the image.
+    fn point(mut self, matrix: ColorMatrix) -> Self {
+        self.point_ops.push(matrix);
+        self
+    }
+
+    /// Apply the queued point-wise operations in one pass, inside the region
+    /// they were queued under.
+    fn flush(mut self) -> Self {
+        let point_ops = std::mem::take(&mut self.point_ops);
+        if point_ops.is_empty() {
+            return self;
+        }
+        self.filter_in_place(|image| point_ops.apply(image))
+    }
+
+    /// Run a transformation on the whole image.
+    fn transform(self, transform: impl FnOnce(&DynamicImage) -> DynamicImage) -> Self {
+        let mut processor = self.flush();
+        processor.image = transform(&processor.image);
+        processor
+    }
+
+    /// Run a resize on the whole image, sharpening it if asked to when both
+    /// sides shrank.
+    fn resample(self, resample: impl FnOnce(&DynamicImage) -> DynamicImage) -> Self {
+        let (width, height) = (self.image.width(), self.image.height());
+        let mut processor = self.transform(resample);
+        let downscaled = processor.image.width() < width && processor.image.height() < height;
+        if processor.auto_sharpen && downscaled {
+            processor.image = sharpen::apply_mild(&processor.image);
+        }
+        processor
+    }
+
+    /// Run a filter, keeping it inside the current region if there is one.
+    fn filter(self, filter: impl FnOnce(&DynamicImage) -> DynamicImage) -> Self {
+        let mut processor = self.flush();
+        let filtered = filter(&processor.image);
+        processor.image = match &processor.region {
+            Some(region) => region.blend(&processor.image, filtered),
+            None => filtered,
+        };
+        processor
+    }
+
+    /// Like `filter`, for filters that change the image in place.
+    /// Does not apply queued point-wise operations, `flush` relies on that.
+    fn filter_in_place(mut self, filter: impl FnOnce(&mut DynamicImage)) -> Self {
+        match &self.region {
+            Some(region) => {
+                let mut filtered = self.image.clone();
+                filter(&mut filtered);
+                self.image = region.blend(&self.image, filtered);
+            }
+            None => filter(&mut self.image),
+        }
+        self
     }
 }
 
@@ -170,17 +685,365 @@ mod tests {
     fn test_chaining() {
         let test_image_data = include_bytes!("../../sample.jpg").to_vec();
         let result = ImageProcessor::new(test_image_data)
+            .unwrap()
             .resize(512, 512)
             .unwrap()
             .grayscale()
             .unwrap()
             .contrast(25.0)
             .unwrap()
-            .process();
+            .process()
+            .unwrap();
 
         let result_image = image::load_from_memory(&result).unwrap();
         assert_eq!(result_image.width(), 512);
 
         result_image.save("test-output/chaining.jpg").unwrap();
     }
+
+    #[test]
+    fn test_rgba_round_trip() {
+        let (width, height) = (4, 2);
+        let data: Vec<u8> = (0..width * height * 4).map(|v| v as u8 * 8).collect();
+
+        let pixels = ImageProcessor::from_rgba(width, height, data.clone())
+            .unwrap()
+            .invert()
+            .unwrap()
+            .to_rgba();
+
+        assert_eq!(pixels.width(), width);
+        assert_eq!(pixels.height(), height);
+        for (inverted, original) in pixels.data().0.chunks(4).zip(data.chunks(4)) {
+            assert_eq!(inverted[0], 255 - original[0]);
+            assert_eq!(inverted[3], original[3]);
+        }
+    }
+
+    #[test]
+    fn test_css_filter_chain() {
+        let pixels = ImageProcessor::from_rgba(2, 2, vec![200; 2 * 2 * 4])
+            .unwrap()
+            .css_filter("brightness(50%) opacity(0.5)")
+            .unwrap()
+            .to_rgba();
+
+        for pixel in pixels.data().0.chunks(4) {
+            assert_eq!(pixel, [100, 100, 100, 100]);
+        }
+    }
+
+    #[test]
+    fn test_region_change_splits_point_ops() {
+        let pixels = ImageProcessor::from_rgba(4, 1, vec![100; 4 * 4])
+            .unwrap()
+            .region(Region::rect(0, 0, 2, 1))
+            .invert()
+            .unwrap()
+            .clear_region()
+            .brighten(10)
+            .unwrap()
+            .to_rgba();
+
+        let data = pixels.data().0;
+        assert_eq!(data[..3], [165, 165, 165]);
+        assert_eq!(data[12..15], [110, 110, 110]);
+    }
+
+    #[test]
+    fn test_rgba_to_png() {
+        let data = vec![255; 16 * 16 * 4];
+        let result = ImageProcessor::from_rgba(16, 16, data)
+            .unwrap()
+            .grayscale()
+            .unwrap()
+            .process()
+            .unwrap();
+
+        assert_eq!(image::guess_format(&result).unwrap(), ImageFormat::Png);
+    }
+
+    #[test]
+    fn test_buffer_round_trip() {
+        let test_image_data = include_bytes!("../../sample.jpg").to_vec();
+        let buffer = WasmBuffer::from(test_image_data);
+
+        let output = ImageProcessor::from_buffer(&buffer)
+            .unwrap()
+            .thumbnail(64, 64)
+            .unwrap()
+            .process_to_buffer()
+            .unwrap();
+
+        let result_image = image::load_from_memory(output.as_slice()).unwrap();
+        assert_eq!(result_image.width(), 64);
+    }
+
+    #[test]
+    fn test_rgba_buffer_is_not_copied() {
+        let buffer = WasmBuffer::from(vec![128; 32 * 32 * 4]);
+        let ptr = buffer.ptr();
+
+        let output = ImageProcessor::from_rgba_buffer(32, 32, buffer)
+            .unwrap()
+            .to_rgba_buffer();
+
+        assert_eq!(output.ptr(), ptr);
+        assert_eq!(output.len(), 32 * 32 * 4);
+    }
+
+    #[test]
+    fn test_region_blur() {
+        let test_image_data = include_bytes!("../../sample.jpg").to_vec();
+        let original = ImageProcessor::new(test_image_data)
+            .unwrap()
+            .resize(256, 256)
+            .unwrap();
+        let original_pixels = original.image.to_rgb8();
+
+        let result = original
+            .region(Region::rect(0, 0, 128, 256).feather(16.0))
+            .blur(6.0)
+            .unwrap()
+            .clear_region()
+            .into_image()
+            .to_rgb8();
+
+        assert_ne!(
+            result.get_pixel(32, 128),
+            original_pixels.get_pixel(32, 128)
+        );
+        assert_eq!(
+            result.get_pixel(220, 128),
+            original_pixels.get_pixel(220, 128)
+        );
+
+        result.save("test-output/region_blur.jpg").unwrap();
+    }
+
+    #[test]
+    fn test_region_pixelate_removes_detail() {
+        let checkerboard = RgbaImage::from_fn(32, 32, |x, y| {
+            let value = if (x + y) % 2 == 0 { 255 } else { 0 };
+            image::Rgba([value, value, value, 255])
+        });
+
+        let result = ImageProcessor::from_rgba(32, 32, checkerboard.into_raw())
+            .unwrap()
+            .region(Region::rect(8, 8, 16, 16))
+            .pixelate(8)
+            .unwrap()
+            .into_image()
+            .to_rgba8();
+
+        for y in 8..24 {
+            for x in 8..24 {
+                assert_eq!(result.get_pixel(x, y)[0], 128);
+            }
+        }
+        assert_eq!(result.get_pixel(0, 0)[0], 255);
+        assert_eq!(result.get_pixel(1, 0)[0], 0);
+    }
+
+    #[test]
+    fn test_auto_sharpen_after_downscale() {
+        let test_image_data = include_bytes!("../../sample.jpg").to_vec();
+        let soft = ImageProcessor::new(test_image_data.clone())
+            .unwrap()
+            .thumbnail(256, 256)
+            .unwrap()
+            .into_image();
+        let sharp = ImageProcessor::new(test_image_data)
+            .unwrap()
+            .auto_sharpen(true)
+            .thumbnail(256, 256)
+            .unwrap()
+            .into_image();
+
+        assert_eq!(soft.width(), sharp.width());
+        assert_ne!(soft, sharp);
+
+        sharp.save("test-output/auto_sharpen.jpg").unwrap();
+    }
+
+    #[test]
+    fn test_auto_sharpen_skips_crops() {
+        let test_image_data = include_bytes!("../../sample.jpg").to_vec();
+        let plain = ImageProcessor::new(test_image_data.clone())
+            .unwrap()
+            .crop(10, 10, 100, 80)
+            .unwrap()
+            .into_image();
+        let cropped = ImageProcessor::new(test_image_data)
+            .unwrap()
+            .auto_sharpen(true)
+            .crop(10, 10, 100, 80)
+            .unwrap()
+            .into_image();
+
+        assert_eq!(plain, cropped);
+    }
+
+    #[test]
+    fn test_resize_quality_uses_linear_light() {
+        let lines = RgbaImage::from_fn(64, 64, |x, _| {
+            let value = if x % 2 == 0 { 255 } else { 0 };
+            image::Rgba([value, value, value, 255])
+        });
+
+        let result = ImageProcessor::from_rgba(64, 64, lines.into_raw())
+            .unwrap()
+            .resize_quality(ResizeQuality::Best)
+            .resize(16, 16)
+            .unwrap()
+            .into_image()
+            .to_rgba8();
+
+        assert_eq!(result.dimensions(), (16, 16));
+        assert!(result.get_pixel(8, 8)[0].abs_diff(188) <= 3);
+
+        // The resize quality leaves the blur setting alone, whatever the order.
+        let processor = || ImageProcessor::from_rgba(1, 1, vec![0; 4]).unwrap();
+        assert!(
+            !processor()
+                .linear_light(false)
+                .resize_quality(ResizeQuality::Best)
+                .linear_light
+        );
+        assert!(
+            processor()
+                .linear_light(true)
+                .resize_quality(ResizeQuality::Fast)
+                .linear_light
+        );
+    }
+
+    #[test]
+    fn test_operations_keep_bit_depth() {
+        type Op = fn(ImageProcessor) -> Result<ImageProcessor, JsError>;
+        let ops: Vec<(&str, Op)> = vec![
+            ("resize", |p| p.resize(20, 20)),
+            ("resize_square", |p| p.resize_square(20)),
+            ("thumbnail", |p| p.thumbnail(20, 20)),
+            ("crop", |p| p.crop(1, 1, 20, 20)),
+            ("blur", |p| p.blur(2.0)),
+            ("fast_blur", |p| p.fast_blur(2.0)),
+            ("brighten", |p| p.brighten(10)),
+            ("contrast", |p| p.contrast(10.0)),
+            ("grayscale", |p| p.grayscale()),
+            ("threshold", |p| p.threshold(128.0)),
+            ("otsu_threshold", |p| p.otsu_threshold()),
+            ("adaptive_threshold", |p| p.adaptive_threshold(15, 10.0)),
+            ("sauvola_threshold", |p| p.sauvola_threshold(15, 0.3)),
+            ("invert", |p| p.invert()),
+            ("sepia", |p| p.sepia(0.5)),
+            ("saturate", |p| p.saturate(0.5)),
+            ("opacity", |p| p.opacity(0.5)),
+            ("drop_shadow", |p| p.drop_shadow(2.0, 2.0, 2.0, "black")),
+            ("css_filter", |p| p.css_filter("sepia(50%) blur(2px)")),
+            ("detect_edges", |p| p.detect_edges(EdgeOperator::Sobel)),
+            ("canny", |p| p.canny(0.1, 0.3)),
+            ("hue_rotate", |p| p.hue_rotate(30)),
+            ("sharpen", |p| p.sharpen(0.5)),
+            ("unsharpen", |p| p.unsharpen(1.0, 1.0, 0)),
+            ("median", |p| p.median(1)),
+            ("bilateral", |p| p.bilateral(2.0, 20.0)),
+            ("pixelate", |p| p.pixelate(4)),
+            ("exposure", |p| p.exposure(1.0)),
+            ("shadows", |p| p.shadows(30.0)),
+            ("auto_wb", |p| p.auto_white_balance(WhiteBalance::GrayWorld)),
+            ("saturation", |p| p.saturation(20.0)),
+            ("auto_levels", |p| p.auto_levels(1.0, 1.0, false)),
+            ("auto_contrast", |p| p.auto_contrast(1.0, 1.0)),
+            ("equalize", |p| p.equalize()),
+            ("clahe", |p| p.clahe(8, 4.0)),
+            ("levels", |p| p.levels(10.0, 240.0, 1.2, 0.0, 255.0)),
+            ("vignette", |p| p.vignette(0.5)),
+            ("grain", |p| p.grain(0.1)),
+            ("preset", |p| p.apply_preset("vintage", 1.0)),
+            ("convolve", |p| {
+                p.convolve(
+                    vec![0.0, -1.0, 0.0, -1.0, 5.0, -1.0, 0.0, -1.0, 0.0],
+                    1.0,
+                    0.0,
+                    EdgeMode::Clamp,
+                    "rgb",
+                )
+            }),
+        ];
+        let gradient = DynamicImage::ImageRgb8(image::RgbImage::from_fn(32, 32, |x, y| {
+            image::Rgb([x as u8 * 8, y as u8 * 8, 100])
+        }));
+        let bits = |color: ColorType| color.bytes_per_pixel() / color.channel_count();
+        // Float images cannot be encoded for `new`, so build the processor directly.
+        let processor = |image: &DynamicImage| ImageProcessor {
+            image: image.clone(),
+            format: ImageFormat::Png,
+            region: None,
+            auto_sharpen: false,
+            quality: ResizeQuality::default(),
+            linear_light: false,
+            indexed_png: false,
+            point_ops: PointOps::default(),
+        };
+
+        for image in [
+            DynamicImage::ImageRgb16(gradient.to_rgb16()),
+            DynamicImage::ImageRgba32F(gradient.to_rgba32f()),
+        ] {
+            for (name, op) in &ops {
+                let color = op(processor(&image)).unwrap().into_image().color();
+                assert_eq!(
+                    bits(color),
+                    bits(image.color()),
+                    "{name} turned {:?} into {color:?}",
+                    image.color()
+                );
+            }
+        }
+
+        let reduced = processor(&DynamicImage::ImageRgb16(gradient.to_rgb16()))
+            .to_8bit(true)
+            .unwrap()
+            .into_image();
+        assert_eq!(reduced.color(), ColorType::Rgb8);
+
+        // 16-bit values are kept, not rounded to fewer levels.
+        let ramp =
+            image::ImageBuffer::from_fn(256, 256, |x, y| image::Luma([(y * 256 + x) as u16]));
+        let median = processor(&DynamicImage::ImageLuma16(ramp))
+            .median(1)
+            .unwrap()
+            .into_image()
+            .into_luma16();
+        let mut levels = median.into_raw();
+        levels.sort_unstable();
+        levels.dedup();
+        assert!(levels.len() > 4096, "{} levels", levels.len());
+    }
+
+    #[test]
+    fn test_indexed_png_output() {
+        let test_image_data = include_bytes!("../../sample.jpg").to_vec();
+        let photo = ImageProcessor::new(test_image_data)
+            .unwrap()
+            .thumbnail(128, 128)
+            .unwrap()
+            .into_image();
+        let png = write_image(&photo, ImageFormat::Png).unwrap();
+
+        let indexed = ImageProcessor::new(png.clone())
+            .unwrap()
+            .quantize(32, true)
+            .unwrap()
+            .indexed_png(true)
+            .process()
+            .unwrap();
+
+        assert!(indexed.len() < png.len() / 2);
+        let decoder = png::Decoder::new(std::io::Cursor::new(indexed));
+        let info = decoder.read_info().unwrap().info().clone();
+        assert_eq!(info.color_type, png::ColorType::Indexed);
+        assert!(info.palette.unwrap().len() <= 32 * 3);
+    }
 }
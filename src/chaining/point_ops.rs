@@ -0,0 +1,254 @@
+use image::{ColorType, DynamicImage, ImageBuffer, Pixel, Primitive};
+use num_traits::NumCast;
+
+use crate::color_filters::color_matrix::ColorMatrix;
+use crate::utils::pixels::{convert, from_unit, map_rgba, with_buffer};
+
+/// Point-wise color operations queued on `ImageProcessor`, so a run of them
+/// walks the image once instead of once per operation.
+///
+/// Each step clamps its result like the separate filters do, so the outcome
+/// matches running them one by one up to rounding. Steps that can never need
+/// clamping are merged with the next one into a single matrix.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct PointOps {
+    steps: Vec<ColorMatrix>,
+    /// Whether the result is gray and can be stored without color channels,
+    /// like `grayscale` gives it.
+    gray: bool,
+}
+
+impl PointOps {
+    /// Queue a grayscale conversion. The image ends up gray, like `DynamicImage::grayscale`
+    /// gives it, unless a later step tints it again.
+    pub(crate) fn push_grayscale(&mut self) {
+        self.push(ColorMatrix::grayscale_amount(1.0));
+        self.gray = true;
+    }
+
+    pub(crate) fn push(&mut self, matrix: ColorMatrix) {
+        self.gray = self.gray && matrix.keeps_gray();
+        match self.steps.last_mut() {
+            Some(last) if last.stays_in_range() => *last = last.then(&matrix),
+            _ => self.steps.push(matrix),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Run every queued step on the image in one pass.
+    /// Steps that only map each channel on its own become lookup tables
+    /// for 8 and 16-bit images, the rest are evaluated per pixel.
+    pub(crate) fn apply(&self, image: &mut DynamicImage) {
+        if self.steps.is_empty() {
+            return;
+        }
+
+        let color = self
+            .steps
+            .iter()
+            .fold(image.color(), |color, step| step.output_color(color));
+        if image.color() != color {
+            *image = convert(image.clone(), color);
+        }
+
+        let integer = color.bytes_per_pixel() / color.channel_count() <= 2;
+        if integer && self.steps.iter().all(ColorMatrix::is_per_channel) {
+            with_buffer!(image, buffer => self.apply_lut(buffer));
+        } else {
+            map_rgba(image, |rgba| self.evaluate(rgba));
+        }
+
+        if self.gray {
+            let gray = match color {
+                ColorType::Rgb8 => ColorType::L8,
+                ColorType::Rgba8 => ColorType::La8,
+                ColorType::Rgb16 => ColorType::L16,
+                ColorType::Rgba16 => ColorType::La16,
+                // There are no gray float images, those stay RGB.
+                color => color,
+            };
+            *image = convert(image.clone(), gray);
+        }
+    }
+
+    fn evaluate(&self, rgba: [f32; 4]) -> [f32; 4] {
+        self.steps.iter().fold(rgba, |rgba, step| {
+            step.transform(rgba).map(|value| value.clamp(0.0, 1.0))
+        })
+    }
+
+    fn apply_lut<P: Pixel>(&self, buffer: &mut ImageBuffer<P, Vec<P::Subpixel>>) {
+        let levels = lut_levels::<P::Subpixel>();
+        let luts: Vec<Vec<P::Subpixel>> = (0..4)
+            .map(|channel| {
+                (0..levels)
+                    .map(|level| {
+                        let value = level as f32 / (levels - 1) as f32;
+                        from_unit(self.evaluate([value; 4])[channel])
+                    })
+                    .collect()
+            })
+            .collect();
+
+        // Which RGBA lookup table each channel of the pixel type uses.
+        let tables: &[usize] = match P::CHANNEL_COUNT {
+            1 => &[0],
+            2 => &[0, 3],
+            3 => &[0, 1, 2],
+            _ => &[0, 1, 2, 3],
+        };
+
+        for pixel in buffer.pixels_mut() {
+            for (value, &table) in pixel.channels_mut().iter_mut().zip(tables) {
+                let index: usize = NumCast::from(*value).unwrap_or(0);
+                *value = luts[table][index];
+            }
+        }
+    }
+}
+
+fn lut_levels<S: Primitive>() -> usize {
+    NumCast::from(S::DEFAULT_MAX_VALUE).map_or(256, |max: usize| max + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use image::{Rgb, RgbImage};
+
+    use super::*;
+
+    fn gradient() -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(512, 512, |x, y| {
+            Rgb([(x / 2) as u8, (y / 2) as u8, ((x + y) / 4) as u8])
+        }))
+    }
+
+    fn queue(steps: &[ColorMatrix]) -> PointOps {
+        let mut ops = PointOps::default();
+        for step in steps {
+            ops.push(*step);
+        }
+        ops
+    }
+
+    fn one_by_one(image: &DynamicImage, steps: &[ColorMatrix]) -> DynamicImage {
+        let mut image = image.clone();
+        for step in steps {
+            step.apply_in_place(&mut image);
+        }
+        image
+    }
+
+    fn fused(image: &DynamicImage, steps: &[ColorMatrix]) -> DynamicImage {
+        let mut image = image.clone();
+        queue(steps).apply(&mut image);
+        image
+    }
+
+    fn fastest(run: impl Fn()) -> Duration {
+        (0..3)
+            .map(|_| {
+                let start = Instant::now();
+                run();
+                start.elapsed()
+            })
+            .min()
+            .unwrap()
+    }
+
+    fn chains() -> [Vec<ColorMatrix>; 2] {
+        [
+            // Channel independent, runs as a lookup table.
+            vec![
+                ColorMatrix::brighten(20),
+                ColorMatrix::contrast(30.0),
+                ColorMatrix::invert(),
+                ColorMatrix::brighten(-10),
+                ColorMatrix::contrast(-15.0),
+            ],
+            // Mixes channels, runs per pixel.
+            vec![
+                ColorMatrix::brighten(20),
+                ColorMatrix::contrast(30.0),
+                ColorMatrix::hue_rotate(90.0),
+                ColorMatrix::invert(),
+                ColorMatrix::sepia(0.5),
+            ],
+        ]
+    }
+
+    #[test]
+    fn test_fused_matches_one_by_one() {
+        let image = gradient();
+
+        for steps in chains() {
+            let expected = one_by_one(&image, &steps).to_rgb8();
+            let actual = fused(&image, &steps).to_rgb8();
+
+            for (a, b) in actual.pixels().zip(expected.pixels()) {
+                for (a, b) in a.0.iter().zip(b.0) {
+                    assert!(a.abs_diff(b) <= 2, "{a} != {b}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_fuses_grayscale() {
+        let image = gradient();
+        let mut ops = queue(&[ColorMatrix::brighten(20)]);
+        ops.push_grayscale();
+        ops.push(ColorMatrix::contrast(30.0));
+
+        let mut fused = image.clone();
+        ops.apply(&mut fused);
+        assert_eq!(fused.color(), ColorType::L8);
+
+        let mut expected = ColorMatrix::brighten(20).apply(&image).grayscale();
+        ColorMatrix::contrast(30.0).apply_in_place(&mut expected);
+        for (a, b) in fused.to_luma8().pixels().zip(expected.to_luma8().pixels()) {
+            assert!(a[0].abs_diff(b[0]) <= 2, "{} != {}", a[0], b[0]);
+        }
+
+        // A tint after the grayscale needs the color channels again.
+        ops.push(ColorMatrix::sepia(1.0));
+        let mut tinted = image.clone();
+        ops.apply(&mut tinted);
+        assert_eq!(tinted.color(), ColorType::Rgb8);
+    }
+
+    #[test]
+    fn test_merges_steps_without_clamping() {
+        let ops = queue(&[
+            ColorMatrix::invert(),
+            ColorMatrix::opacity(0.5),
+            ColorMatrix::brighten(10),
+            ColorMatrix::invert(),
+        ]);
+
+        // The brighten step can overflow, so the last invert stays separate.
+        assert_eq!(ops.steps.len(), 2);
+    }
+
+    // Wall clock timing flakes on loaded machines, run with `cargo test -- --ignored`.
+    #[test]
+    #[ignore = "timing dependent"]
+    fn test_fused_is_faster() {
+        let image = gradient();
+
+        for steps in chains() {
+            let separate = fastest(|| drop(one_by_one(&image, &steps)));
+            let combined = fastest(|| drop(fused(&image, &steps)));
+
+            assert!(
+                combined < separate,
+                "fused took {combined:?}, one by one {separate:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use image::RgbaImage;
+use wasm_bindgen::{Clamped, prelude::wasm_bindgen};
+
+/// Raw RGBA pixels returned by `ImageProcessor.to_rgba`.
+/// `data` is laid out like the `data` of a canvas `ImageData`.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct RgbaPixels {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl RgbaPixels {
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The pixels as a `Uint8ClampedArray`, four bytes per pixel.
+    #[wasm_bindgen(getter)]
+    pub fn data(&self) -> Clamped<Vec<u8>> {
+        Clamped(self.data.clone())
+    }
+}
+
+impl From<RgbaImage> for RgbaPixels {
+    fn from(image: RgbaImage) -> Self {
+        Self {
+            width: image.width(),
+            height: image.height(),
+            data: image.into_raw(),
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use std::io::Cursor;
+
+use image::{DynamicImage, ImageBuffer, Pixel};
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use crate::utils::pixels::{color_channels, map_channels, to_unit, with_buffer};
+use crate::utils::read_image::read_image;
+
+/// Histogram bins for 16-bit and float images, 8-bit images use one per value.
+const FINE_BINS: usize = 4096;
+
+/// Stretch the tones so the darkest pixels become black and the brightest white.
+/// `clip_low` and `clip_high` are the percent of pixels (0 to 50) allowed to clip
+/// at either end, 0.5 ignores a few stray pixels. With `per_channel` every
+/// channel is stretched on its own, which also removes color casts, otherwise
+/// all channels share the bounds of the luminance and colors keep their balance.
+#[wasm_bindgen]
+pub fn auto_levels(
+    image_data: Vec<u8>,
+    clip_low: f32,
+    clip_high: f32,
+    per_channel: bool,
+) -> Result<Vec<u8>, JsValue> {
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    let mut buf = Vec::new();
+
+    apply(&image, clip_low, clip_high, per_channel)
+        .write_to(&mut Cursor::new(&mut buf), format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to adjust levels: {err}")))?;
+
+    Ok(buf)
+}
+
+/// Auto-contrast: stretch the tones like `auto_levels` with all channels
+/// sharing the bounds of the luminance, so colors keep their balance.
+#[wasm_bindgen]
+pub fn auto_contrast(
+    image_data: Vec<u8>,
+    clip_low: f32,
+    clip_high: f32,
+) -> Result<Vec<u8>, JsValue> {
+    auto_levels(image_data, clip_low, clip_high, false)
+}
+
+/// Auto-level an already decoded image.
+pub(crate) fn apply(
+    image: &DynamicImage,
+    clip_low: f32,
+    clip_high: f32,
+    per_channel: bool,
+) -> DynamicImage {
+    let (clip_low, clip_high) = (clip_low.clamp(0.0, 50.0), clip_high.clamp(0.0, 50.0));
+    let bins = if image.color().bytes_per_pixel() / image.color().channel_count() == 1 {
+        256
+    } else {
+        FINE_BINS
+    };
+
+    let bounds: Vec<(f32, f32)> = if per_channel {
+        with_buffer!(image, buffer => channel_histograms(buffer, bins))
+            .iter()
+            .map(|histogram| bounds(histogram, clip_low, clip_high))
+            .collect()
+    } else {
+        let mut histogram = vec![0; bins];
+        for &value in image.to_luma32f().iter() {
+            histogram[bin(value, bins)] += 1;
+        }
+        vec![bounds(&histogram, clip_low, clip_high); 3]
+    };
+
+    let mut image = image.clone();
+    map_channels(&mut image, |channel, value| {
+        let (low, high) = bounds[channel];
+        if high > low {
+            (value - low) / (high - low)
+        } else {
+            value
+        }
+    });
+    image
+}
+
+fn bin(value: f32, bins: usize) -> usize {
+    (value.clamp(0.0, 1.0) * (bins - 1) as f32).round() as usize
+}
+
+fn channel_histograms<P: Pixel>(
+    buffer: &ImageBuffer<P, Vec<P::Subpixel>>,
+    bins: usize,
+) -> Vec<Vec<u32>> {
+    let color_channels = color_channels::<P>();
+    let mut histograms = vec![vec![0; bins]; color_channels];
+
+    for pixel in buffer.pixels() {
+        for (histogram, &value) in histograms.iter_mut().zip(pixel.channels()) {
+            histogram[bin(to_unit(value), bins)] += 1;
+        }
+    }
+
+    histograms
+}
+
+/// The darkest and brightest values left after clipping the given percent of
+/// pixels at each end, in `0.0..=1.0`.
+fn bounds(histogram: &[u32], clip_low: f32, clip_high: f32) -> (f32, f32) {
+    let total: u64 = histogram.iter().map(|&count| count as u64).sum();
+    let last = histogram.len() - 1;
+
+    let low = past_clip(histogram.iter(), total, clip_low).unwrap_or(0);
+    let high =
+        past_clip(histogram.iter().rev(), total, clip_high).map_or(last, |index| last - index);
+    (low as f32 / last as f32, high as f32 / last as f32)
+}
+
+/// Index of the first bin past the given percent of `total` pixels.
+fn past_clip<'a>(
+    mut counts: impl Iterator<Item = &'a u32>,
+    total: u64,
+    clip: f32,
+) -> Option<usize> {
+    let allowed = (total as f64 * clip as f64 / 100.0) as u64;
+    let mut seen = 0;
+    counts.position(|&count| {
+        seen += count as u64;
+        seen > allowed
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use image::{Rgb, RgbImage};
+
+    use super::*;
+
+    #[test]
+    fn test_auto_levels() {
+        let test_image_data = include_bytes!("../../sample.jpg").to_vec();
+        let leveled_bytes = auto_levels(test_image_data, 0.5, 0.5, false).unwrap();
+
+        let leveled_image = image::load_from_memory(&leveled_bytes).unwrap();
+
+        leveled_image.save("test-output/auto_levels.jpg").unwrap();
+    }
+
+    #[test]
+    fn test_auto_levels_stretches() {
+        // A dull image with a blue cast: red spans 60 to 160, blue 100 to 200.
+        let image = DynamicImage::ImageRgb8(RgbImage::from_fn(101, 1, |x, _| {
+            Rgb([60 + x as u8, 80 + x as u8, 100 + x as u8])
+        }));
+
+        let shared = apply(&image, 0.0, 0.0, false).to_rgb8();
+        let (dark, bright) = (shared.get_pixel(0, 0), shared.get_pixel(100, 0));
+        assert!(
+            dark[2] > dark[0] && bright[2] > bright[0],
+            "luminance mode should keep the color balance"
+        );
+        assert!(dark[0] < 5 && bright[2] > 250);
+
+        let per_channel = apply(&image, 0.0, 0.0, true).to_rgb8();
+        assert_eq!(*per_channel.get_pixel(0, 0), Rgb([0, 0, 0]));
+        assert_eq!(*per_channel.get_pixel(100, 0), Rgb([255, 255, 255]));
+    }
+
+    #[test]
+    fn test_clipping_ignores_outliers() {
+        let mut image = RgbImage::from_fn(100, 1, |x, _| Rgb([100 + x as u8 / 2; 3]));
+        image.put_pixel(0, 0, Rgb([0; 3]));
+        image.put_pixel(99, 0, Rgb([255; 3]));
+
+        let leveled = apply(&DynamicImage::ImageRgb8(image), 1.0, 1.0, true).to_rgb8();
+        assert!(leveled.get_pixel(1, 0)[0] < 10);
+        assert!(leveled.get_pixel(98, 0)[0] > 245);
+    }
+}
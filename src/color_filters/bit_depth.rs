@@ -0,0 +1,111 @@
+use std::io::Cursor;
+
+use image::{ColorType, DynamicImage};
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use super::dither::bayer;
+use crate::utils::pixels::{common_color, convert};
+use crate::utils::read_image::read_image;
+
+/// Reduce the image to 8 bits per channel, the only step that throws precision away.
+/// Every other operation keeps the bit depth it was given, so 16-bit PNG scans
+/// stay 16-bit through a whole chain. `dither` adds an ordered pattern below the
+/// 8-bit step, which turns banding in smooth gradients into invisible noise.
+#[wasm_bindgen]
+pub fn to_8bit(image_data: Vec<u8>, dither: bool) -> Result<Vec<u8>, JsValue> {
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    let mut buf = Vec::new();
+
+    apply(&image, ColorType::L8, dither)
+        .write_to(&mut Cursor::new(&mut buf), format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to convert to 8 bits: {err}")))?;
+
+    Ok(buf)
+}
+
+/// Convert the image to 16 bits per channel, for formats like PNG that can store it.
+/// 8-bit images convert exactly; `dither` only matters for float images.
+#[wasm_bindgen]
+pub fn to_16bit(image_data: Vec<u8>, dither: bool) -> Result<Vec<u8>, JsValue> {
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    let mut buf = Vec::new();
+
+    apply(&image, ColorType::L16, dither)
+        .write_to(&mut Cursor::new(&mut buf), format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to convert to 16 bits: {err}")))?;
+
+    Ok(buf)
+}
+
+/// Convert an already decoded image to the bit depth of `depth`, keeping its channels.
+pub(crate) fn apply(image: &DynamicImage, depth: ColorType, dither: bool) -> DynamicImage {
+    let target = common_color(depth, image.color());
+    let bits = |color: ColorType| color.bytes_per_pixel() / color.channel_count();
+    if !dither || bits(image.color()) <= bits(target) {
+        return convert(image.clone(), target);
+    }
+
+    let max = if bits(target) == 1 { 255.0 } else { 65535.0 };
+    let width = image.width() as usize;
+    let mut float = convert(
+        image.clone(),
+        common_color(ColorType::Rgb32F, image.color()),
+    );
+    let channels = float.color().channel_count() as usize;
+    let pixels = match &mut float {
+        DynamicImage::ImageRgb32F(buffer) => buffer.chunks_exact_mut(channels),
+        DynamicImage::ImageRgba32F(buffer) => buffer.chunks_exact_mut(channels),
+        _ => unreachable!("the image was converted to f32"),
+    };
+    for (index, pixel) in pixels.enumerate() {
+        let offset = (bayer(8, index % width, index / width) - 0.5) / max;
+        pixel[..3].iter_mut().for_each(|value| *value += offset);
+    }
+    convert(float, target)
+}
+
+#[cfg(test)]
+mod test {
+    use image::{ImageBuffer, Rgb};
+
+    use super::*;
+
+    #[test]
+    fn test_to_8bit() {
+        let test_image_data = include_bytes!("../../sample.jpg").to_vec();
+        let converted_bytes = to_8bit(test_image_data, true).unwrap();
+
+        let converted_image = image::load_from_memory(&converted_bytes).unwrap();
+
+        converted_image.save("test-output/to_8bit.jpg").unwrap();
+    }
+
+    #[test]
+    fn test_dithering_keeps_the_average() {
+        // A quarter of the way between 8-bit levels 100 and 101.
+        let value = (100.25 * 257.0) as u16;
+        let image = DynamicImage::ImageRgb16(ImageBuffer::from_pixel(8, 8, Rgb([value; 3])));
+
+        let plain = apply(&image, ColorType::L8, false);
+        assert_eq!(plain.color(), ColorType::Rgb8);
+        assert!(plain.to_rgb8().pixels().all(|pixel| pixel[0] == 100));
+
+        let dithered = apply(&image, ColorType::L8, true).to_rgb8();
+        let mean = dithered.pixels().map(|pixel| pixel[0] as f32).sum::<f32>() / 64.0;
+        assert!((mean - 100.25).abs() < 0.05, "mean {mean}");
+
+        let widened = apply(&plain, ColorType::L16, true);
+        assert_eq!(widened.color(), ColorType::Rgb16);
+        assert_eq!(widened.to_rgb8(), plain.to_rgb8());
+    }
+}
@@ -1,7 +1,10 @@
 use std::io::Cursor;
 
+use image::DynamicImage;
 use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
 
+use crate::utils::color_space::in_linear_light;
+use crate::utils::pixels::premultiplied;
 use crate::utils::read_image::read_image;
 
 /// Performs a Gaussian blur on this image.
@@ -17,17 +20,48 @@ pub fn blur(image_data: Vec<u8>, sigma: f32) -> Result<Vec<u8>, JsValue> {
 
     let mut buf = Vec::new();
 
-    image
-        .blur(sigma)
+    apply(&image, sigma)
         .write_to(&mut Cursor::new(&mut buf), format)
         .map_err(|err| JsValue::from_str(&format!("Failed to blur the image: {err}")))?;
 
     Ok(buf)
 }
 
+/// Like `blur`, but blurs in linear light, so bright lights and edges
+/// spread and glow as they would through a lens instead of turning muddy.
+#[wasm_bindgen]
+pub fn blur_linear(image_data: Vec<u8>, sigma: f32) -> Result<Vec<u8>, JsValue> {
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    let mut buf = Vec::new();
+
+    apply_linear(&image, sigma)
+        .write_to(&mut Cursor::new(&mut buf), format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to blur the image: {err}")))?;
+
+    Ok(buf)
+}
+
+/// Gaussian blur an already decoded image.
+pub(crate) fn apply(image: &DynamicImage, sigma: f32) -> DynamicImage {
+    premultiplied(image, |image| image.blur(sigma))
+}
+
+/// Gaussian blur an already decoded image in linear light.
+pub(crate) fn apply_linear(image: &DynamicImage, sigma: f32) -> DynamicImage {
+    in_linear_light(image, |linear| {
+        premultiplied(linear, |linear| linear.blur(sigma))
+    })
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::utils::pixels::tests::{assert_no_dark_fringe, transparent_sample};
 
     #[test]
     fn test_blur() {
@@ -38,4 +72,27 @@ mod test {
 
         resized_image.save("test-output/blur.jpg").unwrap();
     }
+
+    #[test]
+    fn test_blur_in_linear_light() {
+        // A white line on black keeps its energy when blurred in linear light,
+        // so its surroundings come out brighter than with an sRGB blur.
+        let line = DynamicImage::ImageLuma8(image::GrayImage::from_fn(32, 32, |x, _| {
+            image::Luma([if x == 16 { 255 } else { 0 }])
+        }));
+
+        let gamma = apply(&line, 2.0).to_luma8();
+        let linear = apply_linear(&line, 2.0).to_luma8();
+
+        assert!(linear.get_pixel(18, 16)[0] > gamma.get_pixel(18, 16)[0] + 20);
+        assert_eq!(linear.get_pixel(0, 16)[0], 0);
+    }
+
+    #[test]
+    fn test_blur_transparent_edges() {
+        let logo = transparent_sample();
+
+        assert_no_dark_fringe(&apply(&logo, 3.0));
+        assert_no_dark_fringe(&apply_linear(&logo, 3.0));
+    }
 }
@@ -1,7 +1,9 @@
 use std::io::Cursor;
 
+use image::DynamicImage;
 use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
 
+use super::color_matrix::ColorMatrix;
 use crate::utils::read_image::read_image;
 
 /// Brighten image
@@ -16,14 +18,18 @@ pub fn brighten(image_data: Vec<u8>, value: i32) -> Result<Vec<u8>, JsValue> {
 
     let mut buf = Vec::new();
 
-    image
-        .brighten(value)
+    apply(&image, value)
         .write_to(&mut Cursor::new(&mut buf), format)
         .map_err(|err| JsValue::from_str(&format!("Failed to adjust brightness: {err}")))?;
 
     Ok(buf)
 }
 
+/// Brighten an already decoded image.
+pub(crate) fn apply(image: &DynamicImage, value: i32) -> DynamicImage {
+    ColorMatrix::brighten(value).apply(image)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
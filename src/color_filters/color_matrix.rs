@@ -0,0 +1,334 @@
+use std::io::Cursor;
+
+use image::{ColorType, DynamicImage};
+use serde::Deserialize;
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use crate::utils::pixels::{common_color, convert, map_rgba};
+use crate::utils::read_image::read_image;
+
+/// Transform every pixel with a 4x5 color matrix, like SVG `feColorMatrix type="matrix"`.
+/// `matrix` holds 20 values row by row, for the red, green, blue and alpha outputs.
+/// Each row multiplies `[r, g, b, a, 1]` with channels from 0 to 1, so the last
+/// column is an offset in that range too.
+///
+/// ```javascript
+/// // Swap the red and blue channels.
+/// const swapped = color_matrix(bytes, [
+///   0, 0, 1, 0, 0,
+///   0, 1, 0, 0, 0,
+///   1, 0, 0, 0, 0,
+///   0, 0, 0, 1, 0,
+/// ]);
+/// ```
+#[wasm_bindgen]
+pub fn color_matrix(image_data: Vec<u8>, matrix: Vec<f32>) -> Result<Vec<u8>, JsValue> {
+    let matrix = ColorMatrix::from_slice(&matrix).map_err(|err| JsValue::from_str(&err))?;
+
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    let mut buf = Vec::new();
+
+    matrix
+        .apply(&image)
+        .write_to(&mut Cursor::new(&mut buf), format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to apply color matrix: {err}")))?;
+
+    Ok(buf)
+}
+
+/// A 4x5 matrix over straight RGBA in `0.0..=1.0`.
+/// Filters expressed as a matrix can be combined with `then` and applied in one pass.
+/// Deserializes from a list of 20 values, row by row.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "Vec<f32>")]
+pub(crate) struct ColorMatrix([[f32; 5]; 4]);
+
+impl TryFrom<Vec<f32>> for ColorMatrix {
+    type Error = String;
+
+    fn try_from(values: Vec<f32>) -> Result<Self, String> {
+        Self::from_slice(&values)
+    }
+}
+
+impl ColorMatrix {
+    pub(crate) const IDENTITY: Self = Self([
+        [1.0, 0.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0, 0.0],
+    ]);
+
+    pub(crate) fn from_slice(values: &[f32]) -> Result<Self, String> {
+        if values.len() != 20 {
+            return Err(format!(
+                "A color matrix needs 20 values, got {}",
+                values.len()
+            ));
+        }
+        let mut rows = [[0.0; 5]; 4];
+        for (row, values) in rows.iter_mut().zip(values.chunks_exact(5)) {
+            row.copy_from_slice(values);
+        }
+        Ok(Self(rows))
+    }
+
+    /// Mix the color channels with a 3x3 matrix, keeping alpha.
+    pub(crate) fn rgb(matrix: [[f32; 3]; 3]) -> Self {
+        let mut rows = Self::IDENTITY.0;
+        for (row, values) in rows.iter_mut().zip(matrix) {
+            row[..3].copy_from_slice(&values);
+        }
+        Self(rows)
+    }
+
+    /// Map each color channel to `value * slope + intercept`, keeping alpha.
+    pub(crate) fn linear(slope: f32, intercept: f32) -> Self {
+        let mut rows = Self::IDENTITY.0;
+        for (index, row) in rows.iter_mut().take(3).enumerate() {
+            row[index] = slope;
+            row[4] = intercept;
+        }
+        Self(rows)
+    }
+
+    /// `brighten`: add `value` (in 0 to 255 units) to every color channel.
+    pub(crate) fn brighten(value: i32) -> Self {
+        Self::linear(1.0, value as f32 / 255.0)
+    }
+
+    /// `contrast`: stretch the colors around the middle gray by `((100 + value) / 100)²`.
+    pub(crate) fn contrast(value: f32) -> Self {
+        let slope = ((100.0 + value) / 100.0).powi(2);
+        Self::linear(slope, 0.5 - 0.5 * slope)
+    }
+
+    /// `invert`: flip every color channel, keeping alpha.
+    pub(crate) fn invert() -> Self {
+        Self::linear(-1.0, 1.0)
+    }
+
+    /// CSS `grayscale()`, from the Filter Effects specification.
+    pub(crate) fn grayscale_amount(amount: f32) -> Self {
+        let k = 1.0 - amount.clamp(0.0, 1.0);
+        Self::rgb([
+            [
+                0.2126 + 0.7874 * k,
+                0.7152 - 0.7152 * k,
+                0.0722 - 0.0722 * k,
+            ],
+            [
+                0.2126 - 0.2126 * k,
+                0.7152 + 0.2848 * k,
+                0.0722 - 0.0722 * k,
+            ],
+            [
+                0.2126 - 0.2126 * k,
+                0.7152 - 0.7152 * k,
+                0.0722 + 0.9278 * k,
+            ],
+        ])
+    }
+
+    /// CSS `sepia()`, from the Filter Effects specification.
+    pub(crate) fn sepia(amount: f32) -> Self {
+        let k = 1.0 - amount.clamp(0.0, 1.0);
+        Self::rgb([
+            [0.393 + 0.607 * k, 0.769 - 0.769 * k, 0.189 - 0.189 * k],
+            [0.349 - 0.349 * k, 0.686 + 0.314 * k, 0.168 - 0.168 * k],
+            [0.272 - 0.272 * k, 0.534 - 0.534 * k, 0.131 + 0.869 * k],
+        ])
+    }
+
+    /// CSS `saturate()`, from the Filter Effects specification.
+    pub(crate) fn saturate(amount: f32) -> Self {
+        let s = amount.max(0.0);
+        Self::rgb([
+            [0.213 + 0.787 * s, 0.715 - 0.715 * s, 0.072 - 0.072 * s],
+            [0.213 - 0.213 * s, 0.715 + 0.285 * s, 0.072 - 0.072 * s],
+            [0.213 - 0.213 * s, 0.715 - 0.715 * s, 0.072 + 0.928 * s],
+        ])
+    }
+
+    /// CSS `hue-rotate()`, from the Filter Effects specification.
+    pub(crate) fn hue_rotate(degrees: f32) -> Self {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        Self::rgb([
+            [
+                0.213 + cos * 0.787 - sin * 0.213,
+                0.715 - cos * 0.715 - sin * 0.715,
+                0.072 - cos * 0.072 + sin * 0.928,
+            ],
+            [
+                0.213 - cos * 0.213 + sin * 0.143,
+                0.715 + cos * 0.285 + sin * 0.140,
+                0.072 - cos * 0.072 - sin * 0.283,
+            ],
+            [
+                0.213 - cos * 0.213 - sin * 0.787,
+                0.715 - cos * 0.715 + sin * 0.715,
+                0.072 + cos * 0.928 + sin * 0.072,
+            ],
+        ])
+    }
+
+    /// CSS `opacity()`: scale alpha, keeping the colors.
+    pub(crate) fn opacity(amount: f32) -> Self {
+        let mut rows = Self::IDENTITY.0;
+        rows[3][3] = amount.clamp(0.0, 1.0);
+        Self(rows)
+    }
+
+    /// The matrix that applies `self` and then `next`.
+    /// Only equal to applying both in turn when the first result needs no clamping.
+    pub(crate) fn then(&self, next: &Self) -> Self {
+        let mut rows = [[0.0; 5]; 4];
+        for (row, next_row) in rows.iter_mut().zip(&next.0) {
+            for (column, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| next_row[k] * self.0[k][column]).sum();
+            }
+            row[4] += next_row[4];
+        }
+        Self(rows)
+    }
+
+    /// Whether every pixel in range stays in range, so results never need clamping
+    /// and the matrix can be combined with the next one without changing the output.
+    pub(crate) fn stays_in_range(&self) -> bool {
+        self.0.iter().all(|row| {
+            let low = row[..4].iter().map(|v| v.min(0.0)).sum::<f32>() + row[4];
+            let high = row[..4].iter().map(|v| v.max(0.0)).sum::<f32>() + row[4];
+            low >= -1e-6 && high <= 1.0 + 1e-6
+        })
+    }
+
+    /// Transform one straight RGBA pixel, without clamping.
+    pub(crate) fn transform(&self, rgba: [f32; 4]) -> [f32; 4] {
+        self.0.map(|row| {
+            row[0] * rgba[0] + row[1] * rgba[1] + row[2] * rgba[2] + row[3] * rgba[3] + row[4]
+        })
+    }
+
+    /// Apply the matrix to an already decoded image.
+    pub(crate) fn apply(&self, image: &DynamicImage) -> DynamicImage {
+        let mut image = image.clone();
+        self.apply_in_place(&mut image);
+        image
+    }
+
+    /// Apply the matrix in place. Gray images only become RGB when the matrix
+    /// can tint them, and an alpha channel is only added when it can change.
+    pub(crate) fn apply_in_place(&self, image: &mut DynamicImage) {
+        let color = self.output_color(image.color());
+        if image.color() != color {
+            *image = convert(image.clone(), color);
+        }
+        map_rgba(image, |rgba| self.transform(rgba));
+    }
+
+    /// The color type that can hold the result for an image of type `color`.
+    pub(crate) fn output_color(&self, color: ColorType) -> ColorType {
+        let needed = match (self.keeps_gray(), self.keeps_opaque()) {
+            (true, true) => ColorType::L8,
+            (true, false) => ColorType::La8,
+            (false, true) => ColorType::Rgb8,
+            (false, false) => ColorType::Rgba8,
+        };
+        common_color(color, needed)
+    }
+
+    /// Whether every output channel only depends on the same input channel,
+    /// so the matrix can be applied with a lookup table per channel.
+    pub(crate) fn is_per_channel(&self) -> bool {
+        self.0
+            .iter()
+            .enumerate()
+            .all(|(row, values)| (0..4).all(|column| column == row || values[column] == 0.0))
+    }
+
+    /// Whether gray pixels stay gray, so luma images can stay luma.
+    pub(crate) fn keeps_gray(&self) -> bool {
+        let gray_row = |row: &[f32; 5]| [row[0] + row[1] + row[2], row[3], row[4]];
+        let first = gray_row(&self.0[0]);
+        self.0[1..3].iter().all(|row| {
+            gray_row(row)
+                .iter()
+                .zip(first)
+                .all(|(a, b)| (a - b).abs() < 1e-6)
+        })
+    }
+
+    /// Whether opaque pixels stay opaque, so images without alpha need none.
+    fn keeps_opaque(&self) -> bool {
+        self.0[3] == [0.0, 0.0, 0.0, 1.0, 0.0]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use image::{GrayImage, Luma, Rgb, RgbImage};
+
+    use super::*;
+
+    #[test]
+    fn test_color_matrix() {
+        let test_image_data = include_bytes!("../../sample.jpg").to_vec();
+        #[rustfmt::skip]
+        let swap_red_blue = vec![
+            0.0, 0.0, 1.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0, 0.0,
+            1.0, 0.0, 0.0, 0.0, 0.0,
+            0.0, 0.0, 0.0, 1.0, 0.0,
+        ];
+        let swapped_bytes = color_matrix(test_image_data, swap_red_blue).unwrap();
+
+        let swapped_image = image::load_from_memory(&swapped_bytes).unwrap();
+
+        swapped_image.save("test-output/color_matrix.jpg").unwrap();
+    }
+
+    #[test]
+    fn test_then_matches_sequential() {
+        let pixel = [0.2, 0.4, 0.6, 1.0];
+        let first = ColorMatrix::sepia(0.5);
+        let second = ColorMatrix::contrast(-20.0);
+
+        let fused = first.then(&second).transform(pixel);
+        let sequential = second.transform(first.transform(pixel));
+        for (a, b) in fused.iter().zip(sequential) {
+            assert!((a - b).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_keeps_color_type() {
+        let gray = DynamicImage::ImageLuma8(GrayImage::from_pixel(1, 1, Luma([100])));
+        assert_eq!(ColorMatrix::invert().apply(&gray).color(), ColorType::L8);
+        assert_eq!(
+            ColorMatrix::sepia(1.0).apply(&gray).color(),
+            ColorType::Rgb8
+        );
+        assert_eq!(
+            ColorMatrix::opacity(0.5).apply(&gray).color(),
+            ColorType::La8
+        );
+
+        let rgb = DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, Rgb([10, 20, 30])));
+        let inverted = ColorMatrix::invert().apply(&rgb);
+        assert_eq!(inverted.to_rgb8().get_pixel(0, 0), &Rgb([245, 235, 225]));
+    }
+
+    #[test]
+    fn test_stays_in_range() {
+        assert!(ColorMatrix::IDENTITY.stays_in_range());
+        assert!(ColorMatrix::invert().stays_in_range());
+        assert!(ColorMatrix::grayscale_amount(0.5).stays_in_range());
+        assert!(!ColorMatrix::sepia(1.0).stays_in_range());
+        assert!(!ColorMatrix::brighten(10).stays_in_range());
+    }
+}
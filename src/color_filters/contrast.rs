@@ -1,7 +1,9 @@
 use std::io::Cursor;
 
+use image::DynamicImage;
 use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
 
+use super::color_matrix::ColorMatrix;
 use crate::utils::read_image::read_image;
 
 /// Adjust the contrast of this image.
@@ -17,14 +19,18 @@ pub fn contrast(image_data: Vec<u8>, value: f32) -> Result<Vec<u8>, JsValue> {
 
     let mut buf = Vec::new();
 
-    image
-        .adjust_contrast(value)
+    apply(&image, value)
         .write_to(&mut Cursor::new(&mut buf), format)
         .map_err(|err| JsValue::from_str(&format!("Failed to adjust contrast: {err}")))?;
 
     Ok(buf)
 }
 
+/// Adjust the contrast of an already decoded image.
+pub(crate) fn apply(image: &DynamicImage, value: f32) -> DynamicImage {
+    ColorMatrix::contrast(value).apply(image)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
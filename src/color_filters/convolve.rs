@@ -0,0 +1,261 @@
+use std::io::Cursor;
+
+use image::{DynamicImage, ImageBuffer, Pixel};
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use crate::utils::pixels::{from_unit, map_buffer, to_unit};
+use crate::utils::read_image::read_image;
+
+/// How pixels beyond the image border are read by `convolve`.
+#[wasm_bindgen]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeMode {
+    /// Repeat the nearest edge pixel.
+    #[default]
+    Clamp,
+    /// Continue from the opposite side of the image.
+    Wrap,
+    /// Reflect the image at its edge, without repeating the edge pixel.
+    Mirror,
+}
+
+/// Convolve the image with a custom kernel.
+/// `kernel` holds the weights row by row, it must be square with an odd side (3x3, 5x5, ...).
+/// Each result is divided by `divisor`, 0 divides by the sum of the kernel instead.
+/// `bias` (0 to 255) is added afterwards, 128 is common for emboss kernels.
+/// `channels` picks the channels to filter, any of "r", "g", "b" and "a", like "rgb".
+///
+/// ```javascript
+/// const embossed = convolve(bytes, [-2, -1, 0, -1, 1, 1, 0, 1, 2], 1, 0, EdgeMode.Clamp, "rgb");
+/// ```
+#[wasm_bindgen]
+pub fn convolve(
+    image_data: Vec<u8>,
+    kernel: Vec<f32>,
+    divisor: f32,
+    bias: f32,
+    edge_mode: EdgeMode,
+    channels: &str,
+) -> Result<Vec<u8>, JsValue> {
+    let convolution = Convolution::new(kernel, divisor, bias, edge_mode, channels)
+        .map_err(|err| JsValue::from_str(&err))?;
+
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    let mut buf = Vec::new();
+
+    convolution
+        .apply(&image)
+        .write_to(&mut Cursor::new(&mut buf), format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to convolve the image: {err}")))?;
+
+    Ok(buf)
+}
+
+/// A validated kernel together with the settings to apply it.
+#[derive(Debug, Clone)]
+pub(crate) struct Convolution {
+    kernel: Vec<f32>,
+    side: usize,
+    scale: f32,
+    bias: f32,
+    edge_mode: EdgeMode,
+    channels: [bool; 4],
+}
+
+impl Convolution {
+    pub(crate) fn new(
+        kernel: Vec<f32>,
+        divisor: f32,
+        bias: f32,
+        edge_mode: EdgeMode,
+        channels: &str,
+    ) -> Result<Self, String> {
+        let side = (kernel.len() as f64).sqrt() as usize;
+        if side * side != kernel.len() || side.is_multiple_of(2) {
+            return Err(format!(
+                "The kernel must be square with an odd side, got {} weights",
+                kernel.len()
+            ));
+        }
+
+        let mut selected = [false; 4];
+        for channel in channels.chars() {
+            let index = match channel.to_ascii_lowercase() {
+                'r' => 0,
+                'g' => 1,
+                'b' => 2,
+                'a' => 3,
+                other => return Err(format!("Unknown channel '{other}', use r, g, b or a")),
+            };
+            selected[index] = true;
+        }
+
+        let divisor = match divisor {
+            0.0 => match kernel.iter().sum::<f32>() {
+                0.0 => 1.0,
+                sum => sum,
+            },
+            divisor => divisor,
+        };
+
+        Ok(Self {
+            kernel,
+            side,
+            scale: 1.0 / divisor,
+            bias: bias / 255.0,
+            edge_mode,
+            channels: selected,
+        })
+    }
+
+    /// A 3x3 kernel that filters the color channels, reading clamped edges.
+    pub(crate) fn color_3x3(kernel: [f32; 9]) -> Self {
+        Self {
+            kernel: kernel.to_vec(),
+            side: 3,
+            scale: 1.0,
+            bias: 0.0,
+            edge_mode: EdgeMode::Clamp,
+            channels: [true, true, true, false],
+        }
+    }
+
+    /// Convolve an already decoded image. The bit depth is kept.
+    pub(crate) fn apply(&self, image: &DynamicImage) -> DynamicImage {
+        map_buffer!(image, buffer => self.convolve_buffer(buffer))
+    }
+
+    fn convolve_buffer<P: Pixel>(
+        &self,
+        buffer: &ImageBuffer<P, Vec<P::Subpixel>>,
+    ) -> ImageBuffer<P, Vec<P::Subpixel>> {
+        let (width, height) = buffer.dimensions();
+        let channel_count = P::CHANNEL_COUNT as usize;
+        let selected = self.selected_channels::<P>();
+        let radius = (self.side / 2) as i64;
+        let mut out = buffer.clone();
+
+        for (x, y, pixel) in out.enumerate_pixels_mut() {
+            let mut sums = [0f32; 4];
+            for (index, &weight) in self.kernel.iter().enumerate() {
+                if weight == 0.0 {
+                    continue;
+                }
+                let dx = (index % self.side) as i64 - radius;
+                let dy = (index / self.side) as i64 - radius;
+                let sx = self.edge_mode.resolve(x as i64 + dx, width);
+                let sy = self.edge_mode.resolve(y as i64 + dy, height);
+                let source = buffer.get_pixel(sx, sy).channels();
+
+                for channel in 0..channel_count {
+                    sums[channel] += to_unit(source[channel]) * weight;
+                }
+            }
+
+            for channel in (0..channel_count).filter(|&channel| selected[channel]) {
+                pixel.channels_mut()[channel] = from_unit(sums[channel] * self.scale + self.bias);
+            }
+        }
+
+        out
+    }
+
+    /// Map the r, g, b, a selection onto the channels of the pixel type.
+    fn selected_channels<P: Pixel>(&self) -> [bool; 4] {
+        let [r, g, b, a] = self.channels;
+        match (P::CHANNEL_COUNT, P::HAS_ALPHA) {
+            (1, _) => [r || g || b, false, false, false],
+            (2, _) => [r || g || b, a, false, false],
+            (3, _) => [r, g, b, false],
+            _ => [r, g, b, a],
+        }
+    }
+}
+
+impl EdgeMode {
+    /// Bring a coordinate that may lie outside `0..len` back into the image.
+    pub(crate) fn resolve(self, position: i64, len: u32) -> u32 {
+        let len = len as i64;
+        let position = match self {
+            EdgeMode::Clamp => position.clamp(0, len - 1),
+            EdgeMode::Wrap => position.rem_euclid(len),
+            EdgeMode::Mirror if len == 1 => 0,
+            EdgeMode::Mirror => {
+                let period = 2 * (len - 1);
+                let position = position.rem_euclid(period);
+                if position < len {
+                    position
+                } else {
+                    period - position
+                }
+            }
+        };
+        position as u32
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use image::{ImageBuffer, Rgb};
+
+    use super::*;
+
+    #[test]
+    fn test_convolve_emboss() {
+        let test_image_data = include_bytes!("../../sample.jpg").to_vec();
+        let kernel = vec![-2.0, -1.0, 0.0, -1.0, 1.0, 1.0, 0.0, 1.0, 2.0];
+        let embossed_bytes =
+            convolve(test_image_data, kernel, 1.0, 0.0, EdgeMode::Mirror, "rgb").unwrap();
+
+        let embossed_image = image::load_from_memory(&embossed_bytes).unwrap();
+
+        embossed_image
+            .save("test-output/convolve_emboss.jpg")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_edge_modes() {
+        assert_eq!(EdgeMode::Clamp.resolve(-2, 5), 0);
+        assert_eq!(EdgeMode::Clamp.resolve(7, 5), 4);
+        assert_eq!(EdgeMode::Wrap.resolve(-1, 5), 4);
+        assert_eq!(EdgeMode::Wrap.resolve(5, 5), 0);
+        assert_eq!(EdgeMode::Mirror.resolve(-1, 5), 1);
+        assert_eq!(EdgeMode::Mirror.resolve(5, 5), 3);
+        assert_eq!(EdgeMode::Mirror.resolve(-3, 1), 0);
+    }
+
+    #[test]
+    fn test_box_blur_keeps_depth_and_selected_channels() {
+        let image = ImageBuffer::from_fn(5, 1, |x, _| {
+            Rgb([
+                if x == 2 { 9000 } else { 0 },
+                1234,
+                if x == 2 { 9000 } else { 0 },
+            ])
+        });
+        let convolution = Convolution::new(vec![1.0; 9], 0.0, 0.0, EdgeMode::Clamp, "r").unwrap();
+
+        let blurred = convolution.apply(&DynamicImage::ImageRgb16(image));
+        let DynamicImage::ImageRgb16(blurred) = blurred else {
+            panic!("the bit depth was not kept");
+        };
+
+        assert_eq!(blurred.get_pixel(2, 0)[0], 3000);
+        assert_eq!(blurred.get_pixel(1, 0)[0], 3000);
+        assert_eq!(blurred.get_pixel(0, 0)[0], 0);
+        assert_eq!(blurred.get_pixel(2, 0)[1], 1234);
+        assert_eq!(blurred.get_pixel(2, 0)[2], 9000);
+    }
+
+    #[test]
+    fn test_invalid_kernel() {
+        assert!(Convolution::new(vec![1.0; 4], 0.0, 0.0, EdgeMode::Clamp, "rgb").is_err());
+        assert!(Convolution::new(vec![1.0; 9], 0.0, 0.0, EdgeMode::Clamp, "rgbx").is_err());
+    }
+}
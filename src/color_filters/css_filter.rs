@@ -0,0 +1,413 @@
+use image::{ColorType, DynamicImage, imageops};
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use super::blur;
+use super::color_matrix::ColorMatrix;
+use super::drop_shadow;
+use crate::utils::pixels::{common_color, convert};
+use crate::utils::read_image::read_image;
+use crate::utils::write_image::write_image;
+
+/// Apply a CSS `filter` property value, matching a browser preview of
+/// `<img style="filter: ...">`. Functions run left to right, in sRGB.
+/// Supports `blur`, `brightness`, `contrast`, `drop-shadow`, `grayscale`,
+/// `hue-rotate`, `invert`, `opacity`, `saturate`, `sepia` and `none`.
+/// As in the browser, `blur` fades the borders of the image to transparent.
+///
+/// ```javascript
+/// const output = css_filter(bytes, "sepia(60%) saturate(1.4) blur(2px)");
+/// ```
+#[wasm_bindgen]
+pub fn css_filter(image_data: Vec<u8>, filter: &str) -> Result<Vec<u8>, JsValue> {
+    let functions = parse(filter).map_err(|err| JsValue::from_str(&err))?;
+
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    write_image(&apply(image, &functions), format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to apply CSS filter: {err}")))
+}
+
+/// One function of a CSS filter chain, with its arguments in plain units:
+/// amounts as fractions, lengths in pixels and angles in degrees.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum CssFunction {
+    Blur(f32),
+    Brightness(f32),
+    Contrast(f32),
+    DropShadow {
+        offset_x: f32,
+        offset_y: f32,
+        blur_radius: f32,
+        color: [f32; 4],
+    },
+    Grayscale(f32),
+    HueRotate(f32),
+    Invert(f32),
+    Opacity(f32),
+    Saturate(f32),
+    Sepia(f32),
+}
+
+/// Run a parsed filter chain on an already decoded image.
+/// Color matrices in a row are combined into one pass when that cannot
+/// change the result, that is when the earlier ones never need clamping.
+pub(crate) fn apply(mut image: DynamicImage, functions: &[CssFunction]) -> DynamicImage {
+    let mut pending: Option<ColorMatrix> = None;
+
+    for function in functions {
+        match (function.matrix(), pending) {
+            (Some(matrix), Some(previous)) if previous.stays_in_range() => {
+                pending = Some(previous.then(&matrix));
+            }
+            (Some(matrix), _) => {
+                if let Some(previous) = pending.replace(matrix) {
+                    previous.apply_in_place(&mut image);
+                }
+            }
+            (None, _) => {
+                if let Some(previous) = pending.take() {
+                    previous.apply_in_place(&mut image);
+                }
+                image = function.apply(image);
+            }
+        }
+    }
+
+    if let Some(previous) = pending {
+        previous.apply_in_place(&mut image);
+    }
+    image
+}
+
+/// Blur like a browser does, against transparent black beyond the edges, so
+/// the borders of the image fade out instead of keeping their color.
+/// The result gets an alpha channel.
+fn blur_transparent_edges(image: &DynamicImage, sigma: f32) -> DynamicImage {
+    // The Gaussian kernel has no visible weight past three standard deviations.
+    let pad = (sigma * 3.0).ceil() as u32;
+    let (width, height) = (image.width(), image.height());
+    let color = common_color(image.color(), ColorType::La8);
+
+    let mut padded = DynamicImage::new(width + 2 * pad, height + 2 * pad, color);
+    imageops::replace(
+        &mut padded,
+        &convert(image.clone(), color),
+        pad as i64,
+        pad as i64,
+    );
+    blur::apply(&padded, sigma).crop_imm(pad, pad, width, height)
+}
+
+impl CssFunction {
+    fn apply(&self, mut image: DynamicImage) -> DynamicImage {
+        match *self {
+            CssFunction::Blur(radius) if radius > 0.0 => blur_transparent_edges(&image, radius),
+            CssFunction::Blur(_) => image,
+            CssFunction::DropShadow {
+                offset_x,
+                offset_y,
+                blur_radius,
+                color,
+            } => drop_shadow::apply(&image, offset_x, offset_y, blur_radius, color),
+            _ => {
+                if let Some(matrix) = self.matrix() {
+                    matrix.apply_in_place(&mut image);
+                }
+                image
+            }
+        }
+    }
+
+    /// The color matrix of the function, for all but `blur` and `drop-shadow`.
+    pub(crate) fn matrix(&self) -> Option<ColorMatrix> {
+        let matrix = match *self {
+            CssFunction::Brightness(amount) => ColorMatrix::linear(amount, 0.0),
+            CssFunction::Contrast(amount) => ColorMatrix::linear(amount, 0.5 - 0.5 * amount),
+            CssFunction::Grayscale(amount) => ColorMatrix::grayscale_amount(amount),
+            CssFunction::HueRotate(degrees) => ColorMatrix::hue_rotate(degrees),
+            CssFunction::Invert(amount) => ColorMatrix::linear(1.0 - 2.0 * amount, amount),
+            CssFunction::Opacity(amount) => ColorMatrix::opacity(amount),
+            CssFunction::Saturate(amount) => ColorMatrix::saturate(amount),
+            CssFunction::Sepia(amount) => ColorMatrix::sepia(amount),
+            CssFunction::Blur(_) | CssFunction::DropShadow { .. } => return None,
+        };
+        Some(matrix)
+    }
+}
+
+/// Parse a CSS `filter` value like `"sepia(60%) blur(2px)"`.
+pub(crate) fn parse(filter: &str) -> Result<Vec<CssFunction>, String> {
+    let filter = filter.trim();
+    if filter.is_empty() || filter.eq_ignore_ascii_case("none") {
+        return Ok(Vec::new());
+    }
+
+    split_top_level(filter)?
+        .into_iter()
+        .map(parse_function)
+        .collect()
+}
+
+fn parse_function(function: &str) -> Result<CssFunction, String> {
+    let invalid = || format!("Invalid CSS filter function: {function}");
+    let (name, rest) = function.split_once('(').ok_or_else(invalid)?;
+    let argument = rest.strip_suffix(')').ok_or_else(invalid)?.trim();
+    let name = name.trim().to_ascii_lowercase();
+
+    // Amounts above 100% have no further effect for these functions.
+    let capped = |argument: &str| amount(argument).map(|value| value.min(1.0));
+
+    let parsed = match name.as_str() {
+        "blur" if argument.is_empty() => Some(CssFunction::Blur(0.0)),
+        "blur" => length(argument)
+            .filter(|&px| px >= 0.0)
+            .map(CssFunction::Blur),
+        "brightness" => or_default(argument, amount).map(CssFunction::Brightness),
+        "contrast" => or_default(argument, amount).map(CssFunction::Contrast),
+        "grayscale" => or_default(argument, capped).map(CssFunction::Grayscale),
+        "hue-rotate" if argument.is_empty() => Some(CssFunction::HueRotate(0.0)),
+        "hue-rotate" => angle(argument).map(CssFunction::HueRotate),
+        "invert" => or_default(argument, capped).map(CssFunction::Invert),
+        "opacity" => or_default(argument, capped).map(CssFunction::Opacity),
+        "saturate" => or_default(argument, amount).map(CssFunction::Saturate),
+        "sepia" => or_default(argument, capped).map(CssFunction::Sepia),
+        "drop-shadow" => parse_drop_shadow(argument),
+        _ => return Err(format!("Unknown CSS filter function: {name}")),
+    };
+
+    parsed.ok_or_else(invalid)
+}
+
+fn parse_drop_shadow(argument: &str) -> Option<CssFunction> {
+    let mut lengths = Vec::new();
+    let mut color = None;
+
+    for token in split_top_level(argument).ok()? {
+        match length(token) {
+            Some(px) => lengths.push(px),
+            None if color.is_none() => color = Some(drop_shadow::parse_color(token)?),
+            None => return None,
+        }
+    }
+
+    let (offset_x, offset_y, blur_radius) = match lengths[..] {
+        [x, y] => (x, y, 0.0),
+        [x, y, blur] if blur >= 0.0 => (x, y, blur),
+        _ => return None,
+    };
+
+    Some(CssFunction::DropShadow {
+        offset_x,
+        offset_y,
+        blur_radius,
+        color: color.unwrap_or([0.0, 0.0, 0.0, 1.0]),
+    })
+}
+
+/// Split on whitespace that is not inside parentheses, and after every
+/// top-level closing parenthesis, so `sepia(1)blur(2px)` has two parts like in CSS.
+fn split_top_level(value: &str) -> Result<Vec<&str>, String> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = None;
+
+    for (index, c) in value.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth = depth
+                    .checked_sub(1)
+                    .ok_or_else(|| format!("Unbalanced parentheses in CSS filter: {value}"))?;
+                if depth == 0 {
+                    let start = start.take().unwrap_or(index);
+                    parts.push(&value[start..=index]);
+                    continue;
+                }
+            }
+            c if c.is_whitespace() && depth == 0 => {
+                if let Some(start) = start.take() {
+                    parts.push(&value[start..index]);
+                }
+                continue;
+            }
+            _ => {}
+        }
+        start.get_or_insert(index);
+    }
+
+    if depth != 0 {
+        return Err(format!("Unbalanced parentheses in CSS filter: {value}"));
+    }
+    if let Some(start) = start {
+        parts.push(&value[start..]);
+    }
+    Ok(parts)
+}
+
+/// An omitted amount means 100%.
+fn or_default(argument: &str, parse: impl Fn(&str) -> Option<f32>) -> Option<f32> {
+    if argument.is_empty() {
+        Some(1.0)
+    } else {
+        parse(argument)
+    }
+}
+
+/// A number or percentage, negative values are invalid.
+fn amount(argument: &str) -> Option<f32> {
+    let value = match argument.strip_suffix('%') {
+        Some(percent) => percent.trim().parse::<f32>().ok()? / 100.0,
+        None => argument.parse::<f32>().ok()?,
+    };
+    (value >= 0.0).then_some(value)
+}
+
+/// A length in `px`, or a unitless 0.
+fn length(argument: &str) -> Option<f32> {
+    match argument.strip_suffix("px") {
+        Some(px) => px.parse().ok(),
+        None => argument.parse::<f32>().ok().filter(|&value| value == 0.0),
+    }
+}
+
+/// An angle in degrees, from `deg`, `rad`, `grad` or `turn`, or a unitless 0.
+fn angle(argument: &str) -> Option<f32> {
+    let argument = argument.to_ascii_lowercase();
+    let units = [
+        ("deg", 1.0),
+        ("grad", 0.9),
+        ("rad", 180.0 / std::f32::consts::PI),
+        ("turn", 360.0),
+    ];
+    for (unit, degrees) in units {
+        if let Some(value) = argument.strip_suffix(unit) {
+            return value.parse::<f32>().ok().map(|value| value * degrees);
+        }
+    }
+    argument.parse::<f32>().ok().filter(|&value| value == 0.0)
+}
+
+#[cfg(test)]
+mod test {
+    use image::{Rgb, RgbImage};
+
+    use super::*;
+
+    #[test]
+    fn test_css_filter() {
+        let test_image_data = include_bytes!("../../sample.jpg").to_vec();
+        let filtered_bytes =
+            css_filter(test_image_data, "sepia(60%) saturate(1.4) blur(2px)").unwrap();
+
+        let filtered_image = image::load_from_memory(&filtered_bytes).unwrap();
+
+        filtered_image.save("test-output/css_filter.jpg").unwrap();
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse("sepia(60%) saturate(1.4)  blur(2px) hue-rotate(0.5turn) invert()").unwrap(),
+            vec![
+                CssFunction::Sepia(0.6),
+                CssFunction::Saturate(1.4),
+                CssFunction::Blur(2.0),
+                CssFunction::HueRotate(180.0),
+                CssFunction::Invert(1.0),
+            ]
+        );
+        assert_eq!(
+            parse("drop-shadow(rgba(0, 0, 0, 0.5) 2px 4px 6px) grayscale(250%)").unwrap(),
+            vec![
+                CssFunction::DropShadow {
+                    offset_x: 2.0,
+                    offset_y: 4.0,
+                    blur_radius: 6.0,
+                    color: [0.0, 0.0, 0.0, 0.5],
+                },
+                CssFunction::Grayscale(1.0),
+            ]
+        );
+        assert_eq!(
+            parse("sepia(1)blur(2px)").unwrap(),
+            vec![CssFunction::Sepia(1.0), CssFunction::Blur(2.0)]
+        );
+        assert_eq!(
+            parse("drop-shadow(rgb(0 0 0)2px 4px)").unwrap(),
+            vec![CssFunction::DropShadow {
+                offset_x: 2.0,
+                offset_y: 4.0,
+                blur_radius: 0.0,
+                color: [0.0, 0.0, 0.0, 1.0],
+            }]
+        );
+        assert_eq!(parse("none").unwrap(), vec![]);
+        assert!(parse("brightness(-1)").is_err());
+        assert!(parse("blur(2em)").is_err());
+        assert!(parse("glow(2px)").is_err());
+        assert!(parse("sepia(1").is_err());
+    }
+
+    #[test]
+    fn test_matches_css_math() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, Rgb([100, 150, 200])));
+        let pixel = |filter: &str| {
+            *apply(image.clone(), &parse(filter).unwrap())
+                .to_rgb8()
+                .get_pixel(0, 0)
+        };
+
+        assert_eq!(pixel("brightness(150%)"), Rgb([150, 225, 255]));
+        assert_eq!(pixel("contrast(2)"), Rgb([73, 173, 255]));
+        assert_eq!(pixel("invert(100%)"), Rgb([155, 105, 55]));
+        assert_eq!(pixel("hue-rotate(360deg)"), Rgb([100, 150, 200]));
+        let gray = pixel("grayscale(1)");
+        assert_eq!(gray[0], gray[2]);
+    }
+
+    #[test]
+    fn test_blur_fades_edges() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(32, 32, Rgb([255, 255, 255])));
+        let blurred = apply(image, &parse("blur(3px)").unwrap()).to_rgba8();
+
+        // Like a browser, the edges are blurred against transparent black.
+        let (corner, edge, center) = (
+            blurred.get_pixel(0, 0),
+            blurred.get_pixel(0, 16),
+            blurred.get_pixel(16, 16),
+        );
+        assert!((70..95).contains(&corner[3]), "{corner:?}");
+        assert!((130..160).contains(&edge[3]), "{edge:?}");
+        assert_eq!(center[3], 255);
+        assert!(edge[0] >= 250, "{edge:?}");
+    }
+
+    #[test]
+    fn test_fused_matches_one_by_one() {
+        // Float pixels, so the one by one result is not rounded between steps.
+        let image = DynamicImage::ImageRgb8(RgbImage::from_fn(16, 16, |x, y| {
+            Rgb([(x * 16) as u8, (y * 16) as u8, 200])
+        }));
+        let image = DynamicImage::ImageRgb32F(image.into_rgb32f());
+        let functions =
+            parse("grayscale(50%) invert(1) brightness(0.8) sepia(1) contrast(1.5)").unwrap();
+
+        let fused = apply(image.clone(), &functions).to_rgb32f();
+        let one_by_one = functions
+            .iter()
+            .fold(image, |image, function| {
+                apply(image, std::slice::from_ref(function))
+            })
+            .to_rgb32f();
+
+        for (a, b) in fused.pixels().zip(one_by_one.pixels()) {
+            for (a, b) in a.0.iter().zip(b.0) {
+                assert!((a - b).abs() < 1e-5, "{a} != {b}");
+            }
+        }
+    }
+}
@@ -0,0 +1,251 @@
+use std::io::Cursor;
+
+use image::{ColorType, DynamicImage};
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use crate::utils::pixels::{common_color, convert, map_channels};
+use crate::utils::read_image::read_image;
+
+/// Remap tones with smooth curves through control points.
+/// Each curve is a flat list of `x, y` pairs from 0 to 255, like
+/// `[0, 0, 64, 50, 192, 210, 255, 255]` for a gentle S-curve, and an empty
+/// list leaves it unchanged. The `red`, `green` and `blue` curves run first,
+/// then `master` on all three channels.
+///
+/// The curve passes through every point and never overshoots between them,
+/// so rising points give a curve that keeps rising. Before the first and after
+/// the last point it stays flat.
+#[wasm_bindgen]
+pub fn curves(
+    image_data: Vec<u8>,
+    master: Vec<f32>,
+    red: Vec<f32>,
+    green: Vec<f32>,
+    blue: Vec<f32>,
+) -> Result<Vec<u8>, JsValue> {
+    let curves =
+        Curves::new(&master, &red, &green, &blue).map_err(|err| JsValue::from_str(&err))?;
+
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    let mut buf = Vec::new();
+
+    curves
+        .apply(&image)
+        .write_to(&mut Cursor::new(&mut buf), format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to apply curves: {err}")))?;
+
+    Ok(buf)
+}
+
+/// A master curve and one curve per color channel.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Curves {
+    master: Curve,
+    channels: [Curve; 3],
+}
+
+impl Curves {
+    pub(crate) fn new(
+        master: &[f32],
+        red: &[f32],
+        green: &[f32],
+        blue: &[f32],
+    ) -> Result<Self, String> {
+        Ok(Self {
+            master: Curve::new(master).map_err(|err| format!("Master curve: {err}"))?,
+            channels: [
+                Curve::new(red).map_err(|err| format!("Red curve: {err}"))?,
+                Curve::new(green).map_err(|err| format!("Green curve: {err}"))?,
+                Curve::new(blue).map_err(|err| format!("Blue curve: {err}"))?,
+            ],
+        })
+    }
+
+    /// Apply the curves to an already decoded image.
+    /// Gray images become color when the channel curves differ.
+    pub(crate) fn apply(&self, image: &DynamicImage) -> DynamicImage {
+        let mut image = image.clone();
+        let [red, green, blue] = &self.channels;
+        if !image.color().has_color() && (red != green || green != blue) {
+            image = convert(image.clone(), common_color(image.color(), ColorType::Rgb8));
+        }
+
+        map_channels(&mut image, |channel, value| {
+            self.master.map(self.channels[channel].map(value))
+        });
+        image
+    }
+}
+
+/// A monotone cubic spline (Fritsch-Carlson) through control points in `0.0..=1.0`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Curve {
+    xs: Vec<f32>,
+    ys: Vec<f32>,
+    slopes: Vec<f32>,
+}
+
+impl Curve {
+    /// Build a curve from `x, y` pairs in 0 to 255, in any order.
+    /// No points gives the identity curve.
+    pub(crate) fn new(points: &[f32]) -> Result<Self, String> {
+        if !points.len().is_multiple_of(2) {
+            return Err(format!(
+                "Expected x, y pairs but got {} values",
+                points.len()
+            ));
+        }
+        if points.iter().any(|value| !(0.0..=255.0).contains(value)) {
+            return Err("Curve points must be between 0 and 255".to_string());
+        }
+
+        let mut pairs: Vec<(f32, f32)> = points
+            .chunks_exact(2)
+            .map(|pair| (pair[0] / 255.0, pair[1] / 255.0))
+            .collect();
+        if pairs.is_empty() {
+            pairs = vec![(0.0, 0.0), (1.0, 1.0)];
+        }
+        pairs.sort_by(|a, b| a.0.total_cmp(&b.0));
+        if pairs.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+            return Err("Two curve points have the same x".to_string());
+        }
+
+        let (xs, ys): (Vec<f32>, Vec<f32>) = pairs.into_iter().unzip();
+        let slopes = monotone_slopes(&xs, &ys);
+        Ok(Self { xs, ys, slopes })
+    }
+
+    /// The curve's value at `x`.
+    pub(crate) fn map(&self, x: f32) -> f32 {
+        let last = self.xs.len() - 1;
+        if x <= self.xs[0] {
+            return self.ys[0];
+        }
+        if x >= self.xs[last] {
+            return self.ys[last];
+        }
+
+        let k = self.xs.partition_point(|&point| point <= x) - 1;
+        let h = self.xs[k + 1] - self.xs[k];
+        let t = (x - self.xs[k]) / h;
+        let (t2, t3) = (t * t, t * t * t);
+
+        let y = (2.0 * t3 - 3.0 * t2 + 1.0) * self.ys[k]
+            + (t3 - 2.0 * t2 + t) * h * self.slopes[k]
+            + (-2.0 * t3 + 3.0 * t2) * self.ys[k + 1]
+            + (t3 - t2) * h * self.slopes[k + 1];
+        y.clamp(0.0, 1.0)
+    }
+}
+
+/// Tangents at each point that keep the spline from overshooting,
+/// so it only rises or falls where the points do.
+fn monotone_slopes(xs: &[f32], ys: &[f32]) -> Vec<f32> {
+    let n = xs.len();
+    if n < 2 {
+        return vec![0.0; n];
+    }
+
+    let secants: Vec<f32> = (0..n - 1)
+        .map(|k| (ys[k + 1] - ys[k]) / (xs[k + 1] - xs[k]))
+        .collect();
+
+    let mut slopes = Vec::with_capacity(n);
+    slopes.push(secants[0]);
+    for pair in secants.windows(2) {
+        if pair[0] * pair[1] <= 0.0 {
+            slopes.push(0.0);
+        } else {
+            slopes.push((pair[0] + pair[1]) / 2.0);
+        }
+    }
+    slopes.push(secants[n - 2]);
+
+    for (k, &secant) in secants.iter().enumerate() {
+        if secant == 0.0 {
+            slopes[k] = 0.0;
+            slopes[k + 1] = 0.0;
+            continue;
+        }
+        let (a, b) = (slopes[k] / secant, slopes[k + 1] / secant);
+        let length = a.hypot(b);
+        if length > 3.0 {
+            slopes[k] = 3.0 / length * a * secant;
+            slopes[k + 1] = 3.0 / length * b * secant;
+        }
+    }
+
+    slopes
+}
+
+#[cfg(test)]
+mod test {
+    use image::{Luma, Rgb, RgbImage};
+
+    use super::*;
+
+    #[test]
+    fn test_curves() {
+        let test_image_data = include_bytes!("../../sample.jpg").to_vec();
+        let s_curve = vec![0.0, 0.0, 64.0, 45.0, 192.0, 215.0, 255.0, 255.0];
+        let curves_bytes = curves(
+            test_image_data,
+            s_curve,
+            vec![],
+            vec![],
+            vec![0.0, 20.0, 255.0, 255.0],
+        )
+        .unwrap();
+
+        let curves_image = image::load_from_memory(&curves_bytes).unwrap();
+
+        curves_image.save("test-output/curves.jpg").unwrap();
+    }
+
+    #[test]
+    fn test_curve_is_monotone_and_hits_points() {
+        let points = [
+            0.0, 0.0, 40.0, 10.0, 60.0, 200.0, 180.0, 210.0, 255.0, 255.0,
+        ];
+        let curve = Curve::new(&points).unwrap();
+
+        for pair in points.chunks_exact(2) {
+            assert!((curve.map(pair[0] / 255.0) - pair[1] / 255.0).abs() < 1e-5);
+        }
+        let values: Vec<f32> = (0..=1000)
+            .map(|step| curve.map(step as f32 / 1000.0))
+            .collect();
+        assert!(values.windows(2).all(|pair| pair[1] >= pair[0]));
+
+        let identity = Curve::new(&[]).unwrap();
+        assert!((identity.map(0.3) - 0.3).abs() < 1e-6);
+        assert!(Curve::new(&[10.0, 10.0, 10.0, 20.0]).is_err());
+        assert!(Curve::new(&[10.0]).is_err());
+    }
+
+    #[test]
+    fn test_channel_curves() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 2, Rgb([100; 3])));
+        let curves = Curves::new(
+            &[],
+            &[0.0, 50.0, 255.0, 255.0],
+            &[],
+            &[0.0, 0.0, 255.0, 200.0],
+        )
+        .unwrap();
+        let pixel = *curves.apply(&image).to_rgb8().get_pixel(0, 0);
+
+        assert!(pixel[0] > 100);
+        assert_eq!(pixel[1], 100);
+        assert!(pixel[2] < 100);
+
+        let gray = DynamicImage::ImageLuma8(image::GrayImage::from_pixel(2, 2, Luma([100])));
+        assert_eq!(curves.apply(&gray).color(), ColorType::Rgb8);
+    }
+}
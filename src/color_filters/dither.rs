@@ -0,0 +1,299 @@
+use image::RgbaImage;
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use super::quantize::Quantizer;
+use crate::utils::read_image::read_image;
+
+/// Floyd–Steinberg: the classic, smooth results with fine grain.
+const FLOYD_STEINBERG: &[(isize, usize, f32)] = &[
+    (1, 0, 7.0 / 16.0),
+    (-1, 1, 3.0 / 16.0),
+    (0, 1, 5.0 / 16.0),
+    (1, 1, 1.0 / 16.0),
+];
+
+/// Atkinson: passes on only three quarters of the error, for crisp, high contrast results.
+const ATKINSON: &[(isize, usize, f32)] = &[
+    (1, 0, 1.0 / 8.0),
+    (2, 0, 1.0 / 8.0),
+    (-1, 1, 1.0 / 8.0),
+    (0, 1, 1.0 / 8.0),
+    (1, 1, 1.0 / 8.0),
+    (0, 2, 1.0 / 8.0),
+];
+
+/// Sierra: spreads the error over three rows, smoother than Floyd–Steinberg.
+const SIERRA: &[(isize, usize, f32)] = &[
+    (1, 0, 5.0 / 32.0),
+    (2, 0, 3.0 / 32.0),
+    (-2, 1, 2.0 / 32.0),
+    (-1, 1, 4.0 / 32.0),
+    (0, 1, 5.0 / 32.0),
+    (1, 1, 4.0 / 32.0),
+    (2, 1, 2.0 / 32.0),
+    (-1, 2, 2.0 / 32.0),
+    (0, 2, 3.0 / 32.0),
+    (1, 2, 2.0 / 32.0),
+];
+
+/// How colors between two palette colors are mixed from them.
+#[wasm_bindgen]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Dither {
+    /// Every pixel takes the nearest palette color, gradients band.
+    None,
+    /// Error diffusion with fine, even grain.
+    #[default]
+    FloydSteinberg,
+    /// Error diffusion with more contrast, the classic Macintosh look.
+    Atkinson,
+    /// Error diffusion over three rows, the smoothest.
+    Sierra,
+    /// A repeating 2x2 pattern, coarse and retro.
+    Bayer2,
+    /// A repeating 4x4 pattern.
+    Bayer4,
+    /// A repeating 8x8 pattern, stable between frames and regions unlike error diffusion.
+    Bayer8,
+}
+
+/// Reduce the image to a computed palette of `colors` colors (2 to 256), mixed
+/// with `method`. PNGs come back as indexed color files.
+#[wasm_bindgen]
+pub fn dither(image_data: Vec<u8>, method: Dither, colors: u32) -> Result<Vec<u8>, JsValue> {
+    let quantizer = Quantizer::new(colors, method).map_err(|err| JsValue::from_str(&err))?;
+    dither_with(image_data, &quantizer)
+}
+
+/// Reduce the image to a fixed `palette` of RGB byte triples, mixed with `method`.
+/// `[0, 0, 0, 255, 255, 255]` gives black and white, a Game Boy or e-ink palette
+/// gives their look. Transparent pixels stay transparent when the palette has
+/// fewer than 256 colors. PNGs come back as indexed color files.
+#[wasm_bindgen]
+pub fn dither_to_palette(
+    image_data: Vec<u8>,
+    method: Dither,
+    palette: Vec<u8>,
+) -> Result<Vec<u8>, JsValue> {
+    let quantizer = Quantizer::fixed(&palette, method).map_err(|err| JsValue::from_str(&err))?;
+    dither_with(image_data, &quantizer)
+}
+
+fn dither_with(image_data: Vec<u8>, quantizer: &Quantizer) -> Result<Vec<u8>, JsValue> {
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    quantizer
+        .encode(&image, format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to dither the image: {err}")))
+}
+
+/// The palette index of every pixel, picked by `nearest` after mixing with `method`.
+/// Fully transparent pixels are looked up as transparent black and pass no error on.
+pub(crate) fn indices(
+    rgba: &RgbaImage,
+    method: Dither,
+    palette: &[[u8; 4]],
+    nearest: impl Fn([u8; 4]) -> u8,
+) -> Vec<u8> {
+    match method {
+        Dither::None => rgba
+            .pixels()
+            .map(|pixel| nearest(if pixel[3] == 0 { [0; 4] } else { pixel.0 }))
+            .collect(),
+        Dither::FloydSteinberg => diffuse(rgba, FLOYD_STEINBERG, palette, nearest),
+        Dither::Atkinson => diffuse(rgba, ATKINSON, palette, nearest),
+        Dither::Sierra => diffuse(rgba, SIERRA, palette, nearest),
+        Dither::Bayer2 => ordered(rgba, 2, palette, nearest),
+        Dither::Bayer4 => ordered(rgba, 4, palette, nearest),
+        Dither::Bayer8 => ordered(rgba, 8, palette, nearest),
+    }
+}
+
+/// Threshold of the `size` x `size` Bayer matrix at `x`, `y`, in `0.0..1.0`.
+/// `size` is a power of two.
+pub(crate) fn bayer(size: usize, x: usize, y: usize) -> f32 {
+    let levels = size.trailing_zeros();
+    let mut index = 0;
+    for bit in 0..levels {
+        let (x, y) = ((x >> bit) & 1, (y >> bit) & 1);
+        index = index << 2 | (x ^ y) << 1 | y;
+    }
+    (index as f32 + 0.5) / (size * size) as f32
+}
+
+/// Error diffusion: pick the nearest palette color for every pixel and pass
+/// what it got wrong on to the pixels right and below, weighted by `kernel`.
+fn diffuse(
+    rgba: &RgbaImage,
+    kernel: &[(isize, usize, f32)],
+    palette: &[[u8; 4]],
+    nearest: impl Fn([u8; 4]) -> u8,
+) -> Vec<u8> {
+    let width = rgba.width() as usize;
+    // Errors of the current and the next two rows, with two spare columns on each side.
+    let mut rows = vec![vec![[0f32; 3]; width + 4]; 3];
+    let mut indices = Vec::with_capacity(rgba.len() / 4);
+
+    for row in rgba.rows() {
+        for (x, pixel) in row.enumerate() {
+            let [r, g, b, a] = pixel.0;
+            if a == 0 {
+                indices.push(nearest([0; 4]));
+                continue;
+            }
+
+            let error = rows[0][x + 2];
+            let wanted: [f32; 3] = std::array::from_fn(|c| [r, g, b][c] as f32 + error[c]);
+            let [r, g, b] = wanted.map(|value| value.clamp(0.0, 255.0).round() as u8);
+            let index = nearest([r, g, b, a]);
+            indices.push(index);
+
+            let chosen = palette[index as usize];
+            for &(dx, dy, weight) in kernel {
+                let spread = &mut rows[dy][(x + 2).wrapping_add_signed(dx)];
+                for c in 0..3 {
+                    spread[c] += (wanted[c] - chosen[c] as f32) * weight;
+                }
+            }
+        }
+        rows.rotate_left(1);
+        rows[2].fill([0.0; 3]);
+    }
+    indices
+}
+
+/// Ordered dithering: nudge every pixel by its Bayer threshold before picking
+/// the nearest palette color. The nudge spans the usual gap between palette colors.
+fn ordered(
+    rgba: &RgbaImage,
+    size: usize,
+    palette: &[[u8; 4]],
+    nearest: impl Fn([u8; 4]) -> u8,
+) -> Vec<u8> {
+    let spread = spread(palette);
+    rgba.enumerate_pixels()
+        .map(|(x, y, pixel)| {
+            let [r, g, b, a] = pixel.0;
+            if a == 0 {
+                return nearest([0; 4]);
+            }
+            let offset = (bayer(size, x as usize, y as usize) - 0.5) * spread;
+            let [r, g, b] =
+                [r, g, b].map(|value| (value as f32 + offset).clamp(0.0, 255.0).round() as u8);
+            nearest([r, g, b, a])
+        })
+        .collect()
+}
+
+/// The average distance from each opaque palette color to its closest
+/// neighbour, per channel. Gray ramps of `n` levels give `255 / (n - 1)`.
+fn spread(palette: &[[u8; 4]]) -> f32 {
+    let opaque: Vec<[f32; 3]> = palette
+        .iter()
+        .filter(|color| color[3] == u8::MAX)
+        .map(|&[r, g, b, _]| [r, g, b].map(f32::from))
+        .collect();
+    if opaque.len() < 2 {
+        return 255.0;
+    }
+
+    let total: f32 = opaque
+        .iter()
+        .map(|a| {
+            opaque
+                .iter()
+                .filter(|b| a != *b)
+                .map(|b| (0..3).map(|c| (a[c] - b[c]).powi(2)).sum::<f32>().sqrt())
+                .fold(f32::MAX, f32::min)
+        })
+        .filter(|distance| *distance < f32::MAX)
+        .sum();
+    total / opaque.len() as f32 / 3f32.sqrt()
+}
+
+#[cfg(test)]
+mod test {
+    use image::{DynamicImage, GrayImage, Luma};
+
+    use super::*;
+
+    #[test]
+    fn test_dither() {
+        let test_image_data = include_bytes!("../../sample.jpg").to_vec();
+        let dithered_bytes = dither_to_palette(
+            test_image_data,
+            Dither::Atkinson,
+            vec![15, 56, 15, 48, 98, 48, 139, 172, 15, 155, 188, 15],
+        )
+        .unwrap();
+
+        let dithered_image = image::load_from_memory(&dithered_bytes).unwrap();
+
+        dithered_image.save("test-output/dither.jpg").unwrap();
+    }
+
+    #[test]
+    fn test_bayer_matrices() {
+        assert_eq!(
+            [(0, 0), (1, 0), (0, 1), (1, 1)].map(|(x, y)| bayer(2, x, y) * 4.0 - 0.5),
+            [0.0, 2.0, 3.0, 1.0]
+        );
+        for size in [2, 4, 8] {
+            let mut ranks: Vec<usize> = (0..size * size)
+                .map(|i| (bayer(size, i % size, i / size) * (size * size) as f32) as usize)
+                .collect();
+            ranks.sort_unstable();
+            assert_eq!(ranks, (0..size * size).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_methods_keep_the_average() {
+        let ramp =
+            DynamicImage::ImageLuma8(GrayImage::from_fn(128, 64, |x, _| Luma([x as u8 * 2])));
+        let black_and_white = Quantizer::fixed(&[0, 0, 0, 255, 255, 255], Dither::None).unwrap();
+        let banded = black_and_white.apply(&ramp).to_luma8();
+        assert_eq!(banded.get_pixel(40, 0)[0], 0);
+
+        for method in [
+            Dither::FloydSteinberg,
+            Dither::Atkinson,
+            Dither::Sierra,
+            Dither::Bayer2,
+            Dither::Bayer4,
+            Dither::Bayer8,
+        ] {
+            let quantizer = Quantizer::fixed(&[0, 0, 0, 255, 255, 255], method).unwrap();
+            let dithered = quantizer.apply(&ramp).to_luma8();
+            assert!(
+                dithered
+                    .pixels()
+                    .all(|pixel| pixel[0] == 0 || pixel[0] == 255)
+            );
+
+            // Atkinson drops a quarter of the error, so it clips more towards the ends.
+            let tolerance = if method == Dither::Atkinson {
+                30.0
+            } else {
+                16.0
+            };
+            // Average 8 columns, a whole Bayer tile, around each spot.
+            for start in [24u32, 64, 100] {
+                let mean = (start..start + 8)
+                    .flat_map(|x| (0..64).map(move |y| (x, y)))
+                    .map(|(x, y)| dithered.get_pixel(x, y)[0] as f32)
+                    .sum::<f32>()
+                    / 512.0;
+                let wanted = (start * 2 + 7) as f32;
+                assert!(
+                    (mean - wanted).abs() < tolerance,
+                    "{method:?} at {start}: {mean} for {wanted}"
+                );
+            }
+        }
+    }
+}
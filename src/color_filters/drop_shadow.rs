@@ -0,0 +1,191 @@
+use image::{DynamicImage, ImageBuffer, Luma, Rgba32FImage, imageops};
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use crate::utils::pixels::{common_color, convert};
+use crate::utils::read_image::read_image;
+use crate::utils::write_image::write_image;
+
+/// Draw a blurred shadow of the image's opaque parts behind it, like CSS `drop-shadow()`.
+/// Offsets and `blur_radius` are in pixels, the image keeps its size.
+/// `color` is a CSS color like `"#0008"`, `"rgba(0, 0, 0, 0.5)"` or `"black"`.
+/// Images without transparency get the shadow hidden behind them, use PNG or WebP with alpha.
+#[wasm_bindgen]
+pub fn drop_shadow(
+    image_data: Vec<u8>,
+    offset_x: f32,
+    offset_y: f32,
+    blur_radius: f32,
+    color: &str,
+) -> Result<Vec<u8>, JsValue> {
+    let color = parse_color(color)
+        .ok_or_else(|| JsValue::from_str(&format!("Failed to parse color: {color}")))?;
+
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    write_image(
+        &apply(&image, offset_x, offset_y, blur_radius, color),
+        format,
+    )
+    .map_err(|err| JsValue::from_str(&format!("Failed to add drop shadow: {err}")))
+}
+
+/// Add a drop shadow behind an already decoded image.
+/// `color` is straight (not premultiplied) RGBA in `0.0..=1.0`.
+pub(crate) fn apply(
+    image: &DynamicImage,
+    offset_x: f32,
+    offset_y: f32,
+    blur_radius: f32,
+    color: [f32; 4],
+) -> DynamicImage {
+    let source = image.to_rgba32f();
+    let (width, height) = source.dimensions();
+    let (dx, dy) = (offset_x.round() as i64, offset_y.round() as i64);
+
+    let mut shadow = ImageBuffer::from_fn(width, height, |x, y| {
+        let (sx, sy) = (x as i64 - dx, y as i64 - dy);
+        let inside = (0..width as i64).contains(&sx) && (0..height as i64).contains(&sy);
+        Luma([if inside {
+            source.get_pixel(sx as u32, sy as u32)[3]
+        } else {
+            0.0
+        }])
+    });
+    // The CSS blur radius is twice the standard deviation.
+    if blur_radius > 0.0 {
+        shadow = imageops::blur(&shadow, blur_radius / 2.0);
+    }
+
+    let [sr, sg, sb, sa] = color;
+    let out = Rgba32FImage::from_fn(width, height, |x, y| {
+        let [r, g, b, a] = source.get_pixel(x, y).0;
+        let shadow_alpha = shadow.get_pixel(x, y)[0].clamp(0.0, 1.0) * sa * (1.0 - a);
+        let alpha = a + shadow_alpha;
+        if alpha <= 0.0 {
+            return image::Rgba([0.0; 4]);
+        }
+        let over = |source: f32, shadow: f32| (source * a + shadow * shadow_alpha) / alpha;
+        image::Rgba([over(r, sr), over(g, sg), over(b, sb), alpha])
+    });
+
+    let color = common_color(image.color(), image::ColorType::Rgba8);
+    convert(DynamicImage::ImageRgba32F(out), color)
+}
+
+/// Parse a CSS color into straight RGBA in `0.0..=1.0`.
+/// Supports hex notation, `rgb()`/`rgba()` and a few common names.
+pub(crate) fn parse_color(color: &str) -> Option<[f32; 4]> {
+    let color = color.trim().to_ascii_lowercase();
+
+    if let Some(hex) = color.strip_prefix('#') {
+        let digits: Vec<f32> = hex
+            .chars()
+            .map(|c| c.to_digit(16).map(|d| d as f32))
+            .collect::<Option<_>>()?;
+        return match digits.len() {
+            3 | 4 => {
+                let mut rgba = [1.0; 4];
+                for (value, digit) in rgba.iter_mut().zip(&digits) {
+                    *value = digit * 17.0 / 255.0;
+                }
+                Some(rgba)
+            }
+            6 | 8 => {
+                let mut rgba = [1.0; 4];
+                for (value, pair) in rgba.iter_mut().zip(digits.chunks(2)) {
+                    *value = (pair[0] * 16.0 + pair[1]) / 255.0;
+                }
+                Some(rgba)
+            }
+            _ => None,
+        };
+    }
+
+    if let Some(arguments) = color
+        .strip_prefix("rgba(")
+        .or_else(|| color.strip_prefix("rgb("))
+    {
+        let arguments = arguments.strip_suffix(')')?;
+        let parts: Vec<&str> = arguments
+            .split([',', ' ', '/'])
+            .filter(|part| !part.is_empty())
+            .collect();
+        if !(3..=4).contains(&parts.len()) {
+            return None;
+        }
+
+        let mut rgba = [1.0; 4];
+        for (index, part) in parts.iter().enumerate() {
+            rgba[index] = match (part.strip_suffix('%'), index) {
+                (Some(percent), _) => percent.parse::<f32>().ok()? / 100.0,
+                (None, 3) => part.parse::<f32>().ok()?,
+                (None, _) => part.parse::<f32>().ok()? / 255.0,
+            }
+            .clamp(0.0, 1.0);
+        }
+        return Some(rgba);
+    }
+
+    let named = match color.as_str() {
+        "black" | "currentcolor" => [0, 0, 0, 255],
+        "white" => [255, 255, 255, 255],
+        "transparent" => [0, 0, 0, 0],
+        "red" => [255, 0, 0, 255],
+        "green" => [0, 128, 0, 255],
+        "blue" => [0, 0, 255, 255],
+        "gray" | "grey" => [128, 128, 128, 255],
+        _ => return None,
+    };
+    Some(named.map(|channel| channel as f32 / 255.0))
+}
+
+#[cfg(test)]
+mod test {
+    use image::{Rgba, RgbaImage};
+
+    use super::*;
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("#000"), Some([0.0, 0.0, 0.0, 1.0]));
+        assert_eq!(parse_color("#ff000080").map(|c| c[0]), Some(1.0));
+        assert_eq!(
+            parse_color("rgba(255, 0, 0, 0.5)"),
+            Some([1.0, 0.0, 0.0, 0.5])
+        );
+        assert_eq!(
+            parse_color("rgb(0 0 255 / 50%)"),
+            Some([0.0, 0.0, 1.0, 0.5])
+        );
+        assert_eq!(parse_color("Black"), Some([0.0, 0.0, 0.0, 1.0]));
+        assert_eq!(parse_color("#12"), None);
+        assert_eq!(parse_color("hsl(0, 0%, 0%)"), None);
+    }
+
+    #[test]
+    fn test_shadow_behind_square() {
+        let image = RgbaImage::from_fn(10, 10, |x, y| {
+            if (2..5).contains(&x) && (2..5).contains(&y) {
+                Rgba([255, 255, 255, 255])
+            } else {
+                Rgba([0, 0, 0, 0])
+            }
+        });
+        let shadowed = apply(
+            &DynamicImage::ImageRgba8(image),
+            3.0,
+            3.0,
+            0.0,
+            [1.0, 0.0, 0.0, 1.0],
+        )
+        .to_rgba8();
+
+        assert_eq!(shadowed.get_pixel(3, 3).0, [255, 255, 255, 255]);
+        assert_eq!(shadowed.get_pixel(6, 6).0, [255, 0, 0, 255]);
+        assert_eq!(shadowed.get_pixel(8, 1)[3], 0);
+    }
+}
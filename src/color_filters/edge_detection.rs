@@ -0,0 +1,274 @@
+use std::collections::VecDeque;
+use std::io::Cursor;
+
+use image::{DynamicImage, GrayImage, ImageBuffer, Luma, imageops};
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use super::convolve::EdgeMode;
+use crate::utils::pixels::{gray_like, to_unit};
+use crate::utils::read_image::read_image;
+
+/// Blur applied before Canny to keep noise from being picked up as edges.
+const CANNY_SIGMA: f32 = 1.4;
+
+const SOBEL_X: [f32; 9] = [-1.0, 0.0, 1.0, -2.0, 0.0, 2.0, -1.0, 0.0, 1.0];
+const SOBEL_Y: [f32; 9] = [-1.0, -2.0, -1.0, 0.0, 0.0, 0.0, 1.0, 2.0, 1.0];
+const PREWITT_X: [f32; 9] = [-1.0, 0.0, 1.0, -1.0, 0.0, 1.0, -1.0, 0.0, 1.0];
+const PREWITT_Y: [f32; 9] = [-1.0, -1.0, -1.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0];
+const LAPLACIAN: [f32; 9] = [0.0, 1.0, 0.0, 1.0, -4.0, 1.0, 0.0, 1.0, 0.0];
+
+/// Gradient operator used by `detect_edges`.
+#[wasm_bindgen]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeOperator {
+    /// 3x3 Sobel, weighs the center row and column twice.
+    #[default]
+    Sobel,
+    /// 3x3 Prewitt, weighs all neighbours the same.
+    Prewitt,
+    /// 4-neighbour Laplacian, responds to fine detail in every direction.
+    Laplacian,
+}
+
+/// Return a grayscale map of the edges in the image, brighter where the edge is stronger.
+/// A hard black to white edge comes out white.
+#[wasm_bindgen]
+pub fn detect_edges(image_data: Vec<u8>, operator: EdgeOperator) -> Result<Vec<u8>, JsValue> {
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    let mut buf = Vec::new();
+
+    apply(&image, operator)
+        .write_to(&mut Cursor::new(&mut buf), format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to detect edges: {err}")))?;
+
+    Ok(buf)
+}
+
+/// Return a black and white image with one pixel wide white edges, found with Canny.
+/// `low` and `high` (0 to 255) are the hysteresis thresholds on the edge strength:
+/// edges above `high` are kept, edges above `low` are kept when they connect to one.
+#[wasm_bindgen]
+pub fn canny(image_data: Vec<u8>, low: f32, high: f32) -> Result<Vec<u8>, JsValue> {
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    let mut buf = Vec::new();
+
+    apply_canny_image(&image, low, high)
+        .write_to(&mut Cursor::new(&mut buf), format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to detect edges: {err}")))?;
+
+    Ok(buf)
+}
+
+/// Edge magnitude of an already decoded image, at its bit depth.
+pub(crate) fn apply(image: &DynamicImage, operator: EdgeOperator) -> DynamicImage {
+    let luma = image.to_luma32f();
+    let magnitude: Vec<f32> = match operator {
+        EdgeOperator::Sobel => gradient_magnitude(&luma, &SOBEL_X, &SOBEL_Y, 4.0),
+        EdgeOperator::Prewitt => gradient_magnitude(&luma, &PREWITT_X, &PREWITT_Y, 3.0),
+        EdgeOperator::Laplacian => correlate(&luma, &LAPLACIAN)
+            .into_iter()
+            .map(f32::abs)
+            .collect(),
+    };
+
+    let (width, height) = luma.dimensions();
+    gray_like(image.color(), width, height, magnitude)
+}
+
+/// Canny edges of an already decoded image, at its bit depth.
+pub(crate) fn apply_canny_image(image: &DynamicImage, low: f32, high: f32) -> DynamicImage {
+    let edges = apply_canny(image, low, high);
+    let (width, height) = edges.dimensions();
+    let values = edges.into_raw().into_iter().map(to_unit).collect();
+    gray_like(image.color(), width, height, values)
+}
+
+/// Canny edges of an already decoded image, white on black.
+pub(crate) fn apply_canny(image: &DynamicImage, low: f32, high: f32) -> GrayImage {
+    let luma = imageops::blur(&image.to_luma32f(), CANNY_SIGMA);
+    let (width, height) = luma.dimensions();
+    let (w, h) = (width as usize, height as usize);
+
+    let gx = correlate(&luma, &SOBEL_X);
+    let gy = correlate(&luma, &SOBEL_Y);
+    let magnitude: Vec<f32> = gx.iter().zip(&gy).map(|(x, y)| x.hypot(*y) / 4.0).collect();
+
+    // Keep only the local maxima across the edge, so edges are one pixel wide.
+    let mut thin = vec![0f32; w * h];
+    for y in 0..h {
+        for x in 0..w {
+            let index = y * w + x;
+            let value = magnitude[index];
+            if value == 0.0 {
+                continue;
+            }
+            let (dx, dy) = gradient_step(gx[index], gy[index]);
+            let at = |dx: i64, dy: i64| {
+                let sx = EdgeMode::Clamp.resolve(x as i64 + dx, width) as usize;
+                let sy = EdgeMode::Clamp.resolve(y as i64 + dy, height) as usize;
+                magnitude[sy * w + sx]
+            };
+            if value >= at(dx, dy) && value > at(-dx, -dy) {
+                thin[index] = value;
+            }
+        }
+    }
+
+    let (low, high) = (low.min(high) / 255.0, low.max(high) / 255.0);
+    let mut edges = GrayImage::new(width, height);
+    let mut queue: VecDeque<usize> = (0..w * h).filter(|&i| thin[i] >= high).collect();
+    for &index in &queue {
+        edges.as_mut()[index] = u8::MAX;
+    }
+
+    // Grow the strong edges into the connected weak ones.
+    while let Some(index) = queue.pop_front() {
+        let (x, y) = ((index % w) as i64, (index / w) as i64);
+        for (dx, dy) in NEIGHBOURS {
+            let (nx, ny) = (x + dx, y + dy);
+            if nx < 0 || ny < 0 || nx >= w as i64 || ny >= h as i64 {
+                continue;
+            }
+            let neighbour = ny as usize * w + nx as usize;
+            if edges.as_raw()[neighbour] == 0 && thin[neighbour] >= low && thin[neighbour] > 0.0 {
+                edges.as_mut()[neighbour] = u8::MAX;
+                queue.push_back(neighbour);
+            }
+        }
+    }
+
+    edges
+}
+
+const NEIGHBOURS: [(i64, i64); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+/// The neighbour offset closest to the gradient direction.
+fn gradient_step(gx: f32, gy: f32) -> (i64, i64) {
+    let angle = gy.atan2(gx).to_degrees().rem_euclid(180.0);
+    match angle {
+        a if !(22.5..157.5).contains(&a) => (1, 0),
+        a if a < 67.5 => (1, 1),
+        a if a < 112.5 => (0, 1),
+        _ => (-1, 1),
+    }
+}
+
+fn gradient_magnitude(
+    luma: &ImageBuffer<Luma<f32>, Vec<f32>>,
+    kernel_x: &[f32; 9],
+    kernel_y: &[f32; 9],
+    scale: f32,
+) -> Vec<f32> {
+    let gx = correlate(luma, kernel_x);
+    let gy = correlate(luma, kernel_y);
+    gx.iter()
+        .zip(&gy)
+        .map(|(x, y)| x.hypot(*y) / scale)
+        .collect()
+}
+
+/// Signed response of a 3x3 kernel at every pixel, reading clamped edges.
+fn correlate(luma: &ImageBuffer<Luma<f32>, Vec<f32>>, kernel: &[f32; 9]) -> Vec<f32> {
+    let (width, height) = luma.dimensions();
+    let mut out = Vec::with_capacity(width as usize * height as usize);
+
+    for y in 0..height as i64 {
+        for x in 0..width as i64 {
+            let mut sum = 0.0;
+            for (index, &weight) in kernel.iter().enumerate() {
+                if weight == 0.0 {
+                    continue;
+                }
+                let sx = EdgeMode::Clamp.resolve(x + index as i64 % 3 - 1, width);
+                let sy = EdgeMode::Clamp.resolve(y + index as i64 / 3 - 1, height);
+                sum += luma.get_pixel(sx, sy)[0] * weight;
+            }
+            out.push(sum);
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn step_edge() -> DynamicImage {
+        DynamicImage::ImageLuma8(GrayImage::from_fn(16, 16, |x, _| {
+            Luma([if x < 8 { 0 } else { 255 }])
+        }))
+    }
+
+    #[test]
+    fn test_detect_edges() {
+        let test_image_data = include_bytes!("../../sample.jpg").to_vec();
+        let edges_bytes = detect_edges(test_image_data, EdgeOperator::Sobel).unwrap();
+
+        let edges_image = image::load_from_memory(&edges_bytes).unwrap();
+
+        edges_image.save("test-output/edges_sobel.jpg").unwrap();
+    }
+
+    #[test]
+    fn test_canny() {
+        let test_image_data = include_bytes!("../../sample.jpg").to_vec();
+        let edges_bytes = canny(test_image_data, 20.0, 50.0).unwrap();
+
+        let edges_image = image::load_from_memory(&edges_bytes).unwrap();
+
+        edges_image.save("test-output/edges_canny.jpg").unwrap();
+    }
+
+    #[test]
+    fn test_operators_find_step() {
+        for operator in [
+            EdgeOperator::Sobel,
+            EdgeOperator::Prewitt,
+            EdgeOperator::Laplacian,
+        ] {
+            let edges = apply(&step_edge(), operator).to_luma8();
+
+            assert_eq!(edges.get_pixel(2, 8)[0], 0, "{operator:?} flat area");
+            assert_eq!(edges.get_pixel(13, 8)[0], 0, "{operator:?} flat area");
+            assert!(
+                edges.get_pixel(7, 8)[0] > 100,
+                "{operator:?} missed the edge"
+            );
+        }
+    }
+
+    #[test]
+    fn test_canny_thin_edge() {
+        let edges = apply_canny(&step_edge(), 20.0, 50.0);
+
+        for y in 0..16 {
+            let row: Vec<u8> = (0..16).map(|x| edges.get_pixel(x, y)[0]).collect();
+            assert_eq!(
+                row.iter().filter(|&&v| v == 255).count(),
+                1,
+                "row {y}: {row:?}"
+            );
+            assert!(row.iter().all(|&v| v == 0 || v == 255));
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use std::io::Cursor;
+
+use image::DynamicImage;
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use crate::utils::pixels::map_luminance;
+use crate::utils::read_image::read_image;
+
+/// Luminance histogram bins. Values between bins are interpolated, so 16-bit
+/// images still get smooth tones.
+const BINS: usize = 256;
+
+/// Spread the brightness evenly over the whole range with histogram equalization.
+/// Works on the luminance so colors keep their hue. Strong on dull or badly
+/// exposed images, see `clahe` for a gentler local version.
+#[wasm_bindgen]
+pub fn equalize(image_data: Vec<u8>) -> Result<Vec<u8>, JsValue> {
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    let mut buf = Vec::new();
+
+    apply_equalize(&image)
+        .write_to(&mut Cursor::new(&mut buf), format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to equalize the image: {err}")))?;
+
+    Ok(buf)
+}
+
+/// Contrast limited adaptive histogram equalization (CLAHE).
+/// Equalizes each `tile_size` pixel tile on its own and blends between tiles,
+/// bringing out detail in both shadows and highlights. `clip_limit` caps how
+/// much contrast is added, 1.0 changes nothing and 2.0 to 4.0 suits most photos.
+#[wasm_bindgen]
+pub fn clahe(image_data: Vec<u8>, tile_size: u32, clip_limit: f32) -> Result<Vec<u8>, JsValue> {
+    if tile_size == 0 {
+        return Err(JsValue::from_str("The tile size must be at least 1"));
+    }
+
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    let mut buf = Vec::new();
+
+    apply_clahe(&image, tile_size, clip_limit)
+        .write_to(&mut Cursor::new(&mut buf), format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to equalize the image: {err}")))?;
+
+    Ok(buf)
+}
+
+/// Equalize an already decoded image.
+pub(crate) fn apply_equalize(image: &DynamicImage) -> DynamicImage {
+    let luma = image.to_luma32f();
+    let histogram = histogram(luma.iter().copied());
+    let total: u32 = histogram.iter().sum();
+
+    let mut cdf = cumulative(&histogram);
+    let lowest = cdf
+        .iter()
+        .copied()
+        .find(|&count| count > 0.0)
+        .unwrap_or(0.0);
+    if total as f32 <= lowest {
+        return image.clone();
+    }
+    for value in &mut cdf {
+        *value = ((*value - lowest) / (total as f32 - lowest)).max(0.0);
+    }
+
+    let mut image = image.clone();
+    map_luminance(&mut image, |_, luminance| lookup(&cdf, luminance));
+    image
+}
+
+/// Run CLAHE on an already decoded image.
+pub(crate) fn apply_clahe(image: &DynamicImage, tile_size: u32, clip_limit: f32) -> DynamicImage {
+    let luma = image.to_luma32f();
+    let (width, height) = luma.dimensions();
+    let tiles_x = width.div_ceil(tile_size.max(1)) as usize;
+    let tiles_y = height.div_ceil(tile_size.max(1)) as usize;
+    let tile_width = width as f32 / tiles_x as f32;
+    let tile_height = height as f32 / tiles_y as f32;
+    let clip_limit = clip_limit.max(1.0);
+
+    let tables: Vec<Vec<f32>> = (0..tiles_x * tiles_y)
+        .map(|tile| {
+            let (tx, ty) = (tile % tiles_x, tile / tiles_x);
+            let (x0, x1) = (tx as f32 * tile_width, (tx + 1) as f32 * tile_width);
+            let (y0, y1) = (ty as f32 * tile_height, (ty + 1) as f32 * tile_height);
+            let values = (y0 as u32..y1 as u32)
+                .flat_map(|y| (x0 as u32..x1 as u32).map(move |x| (x, y)))
+                .map(|(x, y)| luma.get_pixel(x, y)[0]);
+
+            let mut histogram = histogram(values);
+            let total: u32 = histogram.iter().sum();
+            clip(&mut histogram, clip_limit * total as f32 / BINS as f32);
+            cumulative(&histogram)
+                .into_iter()
+                .map(|count| count / total.max(1) as f32)
+                .collect()
+        })
+        .collect();
+
+    // Position of a pixel between the two nearest tile centers on one axis.
+    let neighbours = |position: u32, size: f32, tiles: usize| {
+        let center = (position as f32 + 0.5) / size - 0.5;
+        let first = (center.floor().max(0.0) as usize).min(tiles - 1);
+        let second = (first + 1).min(tiles - 1);
+        (first, second, (center - first as f32).clamp(0.0, 1.0))
+    };
+
+    let mut image = image.clone();
+    map_luminance(&mut image, |index, luminance| {
+        let (x, y) = (index as u32 % width, index as u32 / width);
+        let (left, right, wx) = neighbours(x, tile_width, tiles_x);
+        let (top, bottom, wy) = neighbours(y, tile_height, tiles_y);
+        let map = |tx: usize, ty: usize| lookup(&tables[ty * tiles_x + tx], luminance);
+
+        let upper = map(left, top) * (1.0 - wx) + map(right, top) * wx;
+        let lower = map(left, bottom) * (1.0 - wx) + map(right, bottom) * wx;
+        upper * (1.0 - wy) + lower * wy
+    });
+    image
+}
+
+fn histogram(values: impl Iterator<Item = f32>) -> Vec<u32> {
+    let mut histogram = vec![0; BINS];
+    for value in values {
+        histogram[(value.clamp(0.0, 1.0) * (BINS - 1) as f32).round() as usize] += 1;
+    }
+    histogram
+}
+
+fn cumulative(histogram: &[u32]) -> Vec<f32> {
+    histogram
+        .iter()
+        .scan(0, |sum, &count| {
+            *sum += count;
+            Some(*sum as f32)
+        })
+        .collect()
+}
+
+/// Cap every bin at `limit` and share what was cut off evenly between all bins.
+fn clip(histogram: &mut [u32], limit: f32) {
+    let limit = limit.max(1.0) as u32;
+    let excess: u32 = histogram
+        .iter_mut()
+        .map(|count| {
+            let cut = count.saturating_sub(limit);
+            *count -= cut;
+            cut
+        })
+        .sum();
+
+    let (share, rest) = (excess / BINS as u32, excess as usize % BINS);
+    for (index, count) in histogram.iter_mut().enumerate() {
+        *count += share + u32::from(index < rest);
+    }
+}
+
+/// Read a table over `0.0..=1.0` at `value`, interpolating between entries.
+fn lookup(table: &[f32], value: f32) -> f32 {
+    let position = value.clamp(0.0, 1.0) * (table.len() - 1) as f32;
+    let index = (position as usize).min(table.len() - 2);
+    let fraction = position - index as f32;
+    table[index] + (table[index + 1] - table[index]) * fraction
+}
+
+#[cfg(test)]
+mod test {
+    use image::{GrayImage, Luma};
+
+    use super::*;
+
+    #[test]
+    fn test_equalize() {
+        let test_image_data = include_bytes!("../../sample.jpg").to_vec();
+        let equalized_bytes = equalize(test_image_data).unwrap();
+
+        let equalized_image = image::load_from_memory(&equalized_bytes).unwrap();
+
+        equalized_image.save("test-output/equalize.jpg").unwrap();
+    }
+
+    #[test]
+    fn test_clahe() {
+        let test_image_data = include_bytes!("../../sample.jpg").to_vec();
+        let equalized_bytes = clahe(test_image_data, 64, 3.0).unwrap();
+
+        let equalized_image = image::load_from_memory(&equalized_bytes).unwrap();
+
+        equalized_image.save("test-output/clahe.jpg").unwrap();
+    }
+
+    #[test]
+    fn test_equalize_spreads_tones() {
+        let image = DynamicImage::ImageLuma8(GrayImage::from_fn(64, 4, |x, _| {
+            Luma([100 + (x / 2) as u8])
+        }));
+        let equalized = apply_equalize(&image).to_luma8();
+
+        assert_eq!(equalized.get_pixel(0, 0)[0], 0);
+        assert_eq!(equalized.get_pixel(63, 0)[0], 255);
+        let middle = equalized.get_pixel(32, 0)[0];
+        assert!(middle.abs_diff(128) < 10, "middle tone at {middle}");
+        let row: Vec<u8> = (0..64).map(|x| equalized.get_pixel(x, 0)[0]).collect();
+        assert!(row.windows(2).all(|pair| pair[1] >= pair[0]));
+    }
+
+    #[test]
+    fn test_clahe_is_local() {
+        // A dim, low contrast stripe pattern on the left and a bright one on the right.
+        let image = DynamicImage::ImageLuma8(GrayImage::from_fn(64, 64, |x, y| {
+            let base = if x < 32 { 30 } else { 200 };
+            Luma([base + (y % 2) as u8 * 8])
+        }));
+        let spread = |image: &GrayImage, x: u32| {
+            image.get_pixel(x, 9)[0] as i32 - image.get_pixel(x, 8)[0] as i32
+        };
+
+        let limited = apply_clahe(&image, 32, 1.0).to_luma8();
+        let equalized = apply_clahe(&image, 32, 40.0).to_luma8();
+
+        assert!(spread(&limited, 8) <= 10);
+        assert!(spread(&equalized, 8) > 30);
+        assert!(spread(&equalized, 56) > 30);
+        assert!(equalized.get_pixel(56, 8)[0] > equalized.get_pixel(8, 8)[0]);
+    }
+}
@@ -1,7 +1,10 @@
 use std::io::Cursor;
 
+use image::DynamicImage;
 use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
 
+use crate::utils::color_space::in_linear_light;
+use crate::utils::pixels::premultiplied;
 use crate::utils::read_image::read_image;
 
 /// Performs a fast blur on this image.
@@ -17,14 +20,44 @@ pub fn fast_blur(image_data: Vec<u8>, sigma: f32) -> Result<Vec<u8>, JsValue> {
 
     let mut buf = Vec::new();
 
-    image
-        .fast_blur(sigma)
+    apply(&image, sigma)
         .write_to(&mut Cursor::new(&mut buf), format)
         .map_err(|err| JsValue::from_str(&format!("Failed to blur the image: {err}")))?;
 
     Ok(buf)
 }
 
+/// Like `fast_blur`, but blurs in linear light, so bright lights and edges
+/// spread and glow as they would through a lens instead of turning muddy.
+#[wasm_bindgen]
+pub fn fast_blur_linear(image_data: Vec<u8>, sigma: f32) -> Result<Vec<u8>, JsValue> {
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    let mut buf = Vec::new();
+
+    apply_linear(&image, sigma)
+        .write_to(&mut Cursor::new(&mut buf), format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to blur the image: {err}")))?;
+
+    Ok(buf)
+}
+
+/// Fast blur an already decoded image.
+pub(crate) fn apply(image: &DynamicImage, sigma: f32) -> DynamicImage {
+    premultiplied(image, |image| image.fast_blur(sigma))
+}
+
+/// Fast blur an already decoded image in linear light.
+pub(crate) fn apply_linear(image: &DynamicImage, sigma: f32) -> DynamicImage {
+    in_linear_light(image, |linear| {
+        premultiplied(linear, |linear| linear.fast_blur(sigma))
+    })
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -0,0 +1,84 @@
+use std::io::Cursor;
+
+use image::{DynamicImage, ImageBuffer, Pixel};
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use crate::utils::pixels::{color_channels, from_unit, to_unit, with_buffer};
+use crate::utils::read_image::read_image;
+
+/// Add film grain: monochrome noise that is the same for a given image size,
+/// so previews and final exports match.
+/// `amount` is the noise strength, 0.05 is subtle and 0.2 is heavy.
+#[wasm_bindgen]
+pub fn grain(image_data: Vec<u8>, amount: f32) -> Result<Vec<u8>, JsValue> {
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    let mut buf = Vec::new();
+
+    apply(&image, amount)
+        .write_to(&mut Cursor::new(&mut buf), format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to add grain: {err}")))?;
+
+    Ok(buf)
+}
+
+/// Add grain to an already decoded image.
+pub(crate) fn apply(image: &DynamicImage, amount: f32) -> DynamicImage {
+    let mut image = image.clone();
+    let amount = amount.max(0.0);
+    with_buffer!(&mut image, buffer => grain_buffer(buffer, amount));
+    image
+}
+
+fn grain_buffer<P: Pixel>(buffer: &mut ImageBuffer<P, Vec<P::Subpixel>>, amount: f32) {
+    let color_channels = color_channels::<P>();
+
+    for (index, pixel) in buffer.pixels_mut().enumerate() {
+        let noise = (noise(index as u64) - 0.5) * 2.0 * amount;
+        for value in &mut pixel.channels_mut()[..color_channels] {
+            *value = from_unit(to_unit(*value) + noise);
+        }
+    }
+}
+
+/// A repeatable pseudo random value in `0.0..1.0` for every pixel index (SplitMix64).
+fn noise(index: u64) -> f32 {
+    let mut z = index.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+    (z >> 40) as f32 / (1u64 << 24) as f32
+}
+
+#[cfg(test)]
+mod test {
+    use image::{Rgb, RgbImage};
+
+    use super::*;
+
+    #[test]
+    fn test_grain() {
+        let test_image_data = include_bytes!("../../sample.jpg").to_vec();
+        let grain_bytes = grain(test_image_data, 0.08).unwrap();
+
+        let grain_image = image::load_from_memory(&grain_bytes).unwrap();
+
+        grain_image.save("test-output/grain.jpg").unwrap();
+    }
+
+    #[test]
+    fn test_grain_is_repeatable_and_balanced() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(64, 64, Rgb([128; 3])));
+        let first = apply(&image, 0.1).to_rgb8();
+
+        assert_eq!(first, apply(&image, 0.1).to_rgb8());
+        let mean = first.pixels().map(|p| p[0] as f32).sum::<f32>() / (64.0 * 64.0);
+        assert!((mean - 128.0).abs() < 2.0, "mean {mean}");
+        assert!(first.pixels().any(|p| p[0] != 128));
+        assert!(first.pixels().all(|p| p[0] == p[1] && p[1] == p[2]));
+    }
+}
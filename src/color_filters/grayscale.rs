@@ -1,5 +1,6 @@
 use std::io::Cursor;
 
+use image::DynamicImage;
 use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
 
 use crate::utils::read_image::read_image;
@@ -18,14 +19,18 @@ pub fn grayscale(image_data: Vec<u8>) -> Result<Vec<u8>, JsValue> {
 
     let mut buf = Vec::new();
 
-    image
-        .grayscale()
+    apply(&image)
         .write_to(&mut Cursor::new(&mut buf), format)
         .map_err(|err| JsValue::from_str(&format!("Failed to do grayscale: {err}")))?;
 
     Ok(buf)
 }
 
+/// Grayscale an already decoded image.
+pub(crate) fn apply(image: &DynamicImage) -> DynamicImage {
+    image.grayscale()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
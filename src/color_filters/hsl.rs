@@ -0,0 +1,281 @@
+use std::f32::consts::FRAC_PI_2;
+use std::io::Cursor;
+
+use image::DynamicImage;
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use crate::utils::color_space::{oklch_to_srgb, srgb_to_oklch};
+use crate::utils::pixels::map_rgba;
+use crate::utils::read_image::read_image;
+
+/// About the highest OKLCH chroma an sRGB color reaches.
+const MAX_CHROMA: f32 = 0.32;
+
+/// OKLCH hue of typical skin tones, which `vibrance` boosts less.
+const SKIN_HUE: f32 = 55.0;
+
+/// Below this chroma colors fade into gray and hue range changes fade out with them.
+const GRAY_CHROMA: f32 = 0.04;
+
+/// A band of hues for `adjust_hue_range`, like the HSL panel of photo editors.
+/// Neighbouring bands overlap so adjusting all of them the same way adjusts
+/// every color evenly.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HueRange {
+    Red,
+    Orange,
+    Yellow,
+    Green,
+    Aqua,
+    Blue,
+    Purple,
+    Magenta,
+}
+
+impl HueRange {
+    /// Every range with the OKLCH hue at its center, in degrees.
+    const CENTERS: [(HueRange, f32); 8] = [
+        (HueRange::Red, 29.0),
+        (HueRange::Orange, 60.0),
+        (HueRange::Yellow, 110.0),
+        (HueRange::Green, 142.0),
+        (HueRange::Aqua, 195.0),
+        (HueRange::Blue, 264.0),
+        (HueRange::Purple, 300.0),
+        (HueRange::Magenta, 340.0),
+    ];
+
+    /// How much a color with `hue` belongs to this range, from 0 to 1.
+    fn weight(self, hue: f32) -> f32 {
+        let index = Self::CENTERS
+            .iter()
+            .position(|&(range, _)| range == self)
+            .unwrap_or(0);
+        let center = Self::CENTERS[index].1;
+        let (_, previous) = Self::CENTERS[(index + 7) % 8];
+        let (_, next) = Self::CENTERS[(index + 1) % 8];
+
+        let offset = (hue - center + 540.0).rem_euclid(360.0) - 180.0;
+        let width = if offset < 0.0 {
+            (center - previous).rem_euclid(360.0)
+        } else {
+            (next - center).rem_euclid(360.0)
+        };
+        if offset.abs() >= width {
+            return 0.0;
+        }
+        (offset / width * FRAC_PI_2).cos().powi(2)
+    }
+}
+
+/// Change how colorful the image is, -100 (gray) to 100 (twice the color).
+/// Works in the OKLCH space, so brightness and hue stay as they are, unlike `saturate`.
+#[wasm_bindgen]
+pub fn saturation(image_data: Vec<u8>, amount: f32) -> Result<Vec<u8>, JsValue> {
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    let mut buf = Vec::new();
+
+    apply_saturation(&image, amount)
+        .write_to(&mut Cursor::new(&mut buf), format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to change saturation: {err}")))?;
+
+    Ok(buf)
+}
+
+/// Boost (positive) or mute (negative) dull colors more than already vivid
+/// ones, -100 to 100. Skin tones are kept natural.
+#[wasm_bindgen]
+pub fn vibrance(image_data: Vec<u8>, amount: f32) -> Result<Vec<u8>, JsValue> {
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    let mut buf = Vec::new();
+
+    apply_vibrance(&image, amount)
+        .write_to(&mut Cursor::new(&mut buf), format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to change vibrance: {err}")))?;
+
+    Ok(buf)
+}
+
+/// Adjust only the colors in one hue range, for example make the blues of
+/// the sky deeper or shift greens towards yellow.
+/// `hue_shift` is in degrees (-180 to 180), `saturation` and `lightness` go
+/// from -100 to 100.
+#[wasm_bindgen]
+pub fn adjust_hue_range(
+    image_data: Vec<u8>,
+    range: HueRange,
+    hue_shift: f32,
+    saturation: f32,
+    lightness: f32,
+) -> Result<Vec<u8>, JsValue> {
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    let mut buf = Vec::new();
+
+    apply_hue_range(&image, range, hue_shift, saturation, lightness)
+        .write_to(&mut Cursor::new(&mut buf), format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to adjust the hue range: {err}")))?;
+
+    Ok(buf)
+}
+
+/// Change the saturation of an already decoded image.
+pub(crate) fn apply_saturation(image: &DynamicImage, amount: f32) -> DynamicImage {
+    let factor = 1.0 + amount.clamp(-100.0, 100.0) / 100.0;
+    map_oklch(image, |[lightness, chroma, hue]| {
+        [lightness, chroma * factor, hue]
+    })
+}
+
+/// Change the vibrance of an already decoded image.
+pub(crate) fn apply_vibrance(image: &DynamicImage, amount: f32) -> DynamicImage {
+    let amount = amount.clamp(-100.0, 100.0) / 100.0;
+    map_oklch(image, |[lightness, chroma, hue]| {
+        let dullness = (1.0 - chroma / MAX_CHROMA).clamp(0.0, 1.0).powi(2);
+        let skin = (hue - SKIN_HUE + 540.0).rem_euclid(360.0) - 180.0;
+        let skin = if skin.abs() < 30.0 {
+            (skin / 30.0 * FRAC_PI_2).cos().powi(2)
+        } else {
+            0.0
+        };
+        let factor = 1.0 + amount * dullness * (1.0 - 0.5 * skin);
+        [lightness, chroma * factor, hue]
+    })
+}
+
+/// Adjust one hue range of an already decoded image.
+pub(crate) fn apply_hue_range(
+    image: &DynamicImage,
+    range: HueRange,
+    hue_shift: f32,
+    saturation: f32,
+    lightness: f32,
+) -> DynamicImage {
+    let hue_shift = hue_shift.clamp(-180.0, 180.0);
+    let saturation = saturation.clamp(-100.0, 100.0) / 100.0;
+    let lightness = lightness.clamp(-100.0, 100.0) / 100.0;
+
+    map_oklch(image, |[l, chroma, hue]| {
+        let weight = range.weight(hue) * (chroma / GRAY_CHROMA).min(1.0);
+        if weight == 0.0 {
+            return [l, chroma, hue];
+        }
+        let l = if lightness < 0.0 {
+            l * (1.0 + lightness * weight * 0.5)
+        } else {
+            l + (1.0 - l) * lightness * weight * 0.5
+        };
+        [
+            l,
+            chroma * (1.0 + saturation * weight),
+            hue + hue_shift * weight,
+        ]
+    })
+}
+
+/// Run `map` on every pixel as OKLCH, keeping alpha. Gray images have no hue
+/// or chroma to change and come back as they are.
+fn map_oklch(image: &DynamicImage, map: impl Fn([f32; 3]) -> [f32; 3]) -> DynamicImage {
+    let mut image = image.clone();
+    if !image.color().has_color() {
+        return image;
+    }
+
+    map_rgba(&mut image, |[r, g, b, a]| {
+        let [l, chroma, hue] = map(srgb_to_oklch([r, g, b]));
+        let [r, g, b] = oklch_to_srgb([l, chroma.max(0.0), hue]);
+        [r, g, b, a]
+    });
+    image
+}
+
+#[cfg(test)]
+mod test {
+    use image::{Rgb, RgbImage};
+
+    use super::*;
+
+    fn oklch_of(image: &DynamicImage) -> [f32; 3] {
+        let pixel = image.to_rgb32f().get_pixel(0, 0).0;
+        srgb_to_oklch(pixel)
+    }
+
+    fn solid(rgb: [u8; 3]) -> DynamicImage {
+        DynamicImage::ImageRgb32F(
+            DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, Rgb(rgb))).to_rgb32f(),
+        )
+    }
+
+    #[test]
+    fn test_vibrance() {
+        let test_image_data = include_bytes!("../../sample.jpg").to_vec();
+        let vibrant_bytes = vibrance(test_image_data, 60.0).unwrap();
+
+        let vibrant_image = image::load_from_memory(&vibrant_bytes).unwrap();
+
+        vibrant_image.save("test-output/vibrance.jpg").unwrap();
+    }
+
+    #[test]
+    fn test_saturation_keeps_lightness_and_hue() {
+        let image = solid([180, 90, 60]);
+        let [l, chroma, hue] = oklch_of(&image);
+        let [l2, chroma2, hue2] = oklch_of(&apply_saturation(&image, 20.0));
+
+        assert!((l - l2).abs() < 1e-3);
+        assert!((hue - hue2).abs() < 0.5);
+        assert!((chroma2 / chroma - 1.2).abs() < 0.01);
+        assert!(oklch_of(&apply_saturation(&image, -100.0))[1] < 1e-3);
+    }
+
+    #[test]
+    fn test_vibrance_favours_dull_colors() {
+        let dull = solid([110, 120, 150]);
+        let vivid = solid([20, 60, 230]);
+        let gain =
+            |image: &DynamicImage| oklch_of(&apply_vibrance(image, 50.0))[1] / oklch_of(image)[1];
+
+        assert!(gain(&dull) > gain(&vivid) + 0.1);
+    }
+
+    #[test]
+    fn test_hue_range() {
+        let blue = solid([40, 80, 200]);
+        let red = solid([200, 40, 40]);
+
+        let bluer = apply_hue_range(&blue, HueRange::Blue, 0.0, 50.0, 0.0);
+        assert!(oklch_of(&bluer)[1] > oklch_of(&blue)[1] * 1.2);
+        let untouched = apply_hue_range(&red, HueRange::Blue, 90.0, 50.0, 50.0);
+        assert_eq!(untouched.to_rgb8(), red.to_rgb8());
+
+        let muted_red = solid([170, 90, 80]);
+        let shifted = apply_hue_range(&muted_red, HueRange::Red, 30.0, 0.0, 0.0);
+        assert!(oklch_of(&shifted)[2] > oklch_of(&muted_red)[2] + 20.0);
+
+        for hue in (0..360).step_by(5) {
+            let total: f32 = HueRange::CENTERS
+                .iter()
+                .map(|&(range, _)| range.weight(hue as f32))
+                .sum();
+            assert!(
+                (total - 1.0).abs() < 1e-4,
+                "weights at {hue} add to {total}"
+            );
+        }
+    }
+}
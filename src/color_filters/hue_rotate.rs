@@ -1,7 +1,9 @@
 use std::io::Cursor;
 
+use image::DynamicImage;
 use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
 
+use super::color_matrix::ColorMatrix;
 use crate::utils::read_image::read_image;
 
 /// Hue rotate the supplied image.
@@ -18,14 +20,18 @@ pub fn hue_rotate(image_data: Vec<u8>, degrees: i32) -> Result<Vec<u8>, JsValue>
 
     let mut buf = Vec::new();
 
-    image
-        .huerotate(degrees)
+    apply(&image, degrees)
         .write_to(&mut Cursor::new(&mut buf), format)
         .map_err(|err| JsValue::from_str(&format!("Failed to hue rotate: {err}")))?;
 
     Ok(buf)
 }
 
+/// Hue rotate an already decoded image.
+pub(crate) fn apply(image: &DynamicImage, degrees: i32) -> DynamicImage {
+    ColorMatrix::hue_rotate(degrees as f32).apply(image)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
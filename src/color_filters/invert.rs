@@ -1,7 +1,9 @@
 use std::io::Cursor;
 
+use image::DynamicImage;
 use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
 
+use super::color_matrix::ColorMatrix;
 use crate::utils::read_image::read_image;
 
 /// Invert the colors on this image.
@@ -15,7 +17,7 @@ pub fn invert(image_data: Vec<u8>) -> Result<Vec<u8>, JsValue> {
 
     let mut buf = Vec::new();
 
-    image.invert();
+    apply(&mut image);
 
     image
         .write_to(&mut Cursor::new(&mut buf), format)
@@ -24,6 +26,11 @@ pub fn invert(image_data: Vec<u8>) -> Result<Vec<u8>, JsValue> {
     Ok(buf)
 }
 
+/// Invert the colors of an already decoded image in place.
+pub(crate) fn apply(image: &mut DynamicImage) {
+    ColorMatrix::invert().apply_in_place(image);
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -0,0 +1,144 @@
+use std::io::Cursor;
+
+use image::DynamicImage;
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use crate::utils::pixels::map_channels;
+use crate::utils::read_image::read_image;
+
+/// Stretch the tones between the `black` and `white` input points (0 to 255)
+/// to the `out_black`..`out_white` output range.
+/// `gamma` bends the midtones, above 1.0 brightens and below 1.0 darkens them.
+///
+/// `levels(bytes, 20, 235, 1.0, 0, 255)` turns a washed out photo into one
+/// with full blacks and whites.
+#[wasm_bindgen]
+pub fn levels(
+    image_data: Vec<u8>,
+    black: f32,
+    white: f32,
+    gamma: f32,
+    out_black: f32,
+    out_white: f32,
+) -> Result<Vec<u8>, JsValue> {
+    let levels = Levels::new(black, white, gamma, out_black, out_white)
+        .map_err(|err| JsValue::from_str(&err))?;
+
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    let mut buf = Vec::new();
+
+    levels
+        .apply(&image)
+        .write_to(&mut Cursor::new(&mut buf), format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to adjust levels: {err}")))?;
+
+    Ok(buf)
+}
+
+/// A levels adjustment, with every point in `0.0..=1.0`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Levels {
+    black: f32,
+    white: f32,
+    gamma: f32,
+    out_black: f32,
+    out_white: f32,
+}
+
+impl Levels {
+    /// Check and scale the points given in 0 to 255.
+    pub(crate) fn new(
+        black: f32,
+        white: f32,
+        gamma: f32,
+        out_black: f32,
+        out_white: f32,
+    ) -> Result<Self, String> {
+        let points = [black, white, out_black, out_white];
+        if points.iter().any(|point| !(0.0..=255.0).contains(point)) {
+            return Err("Levels points must be between 0 and 255".to_string());
+        }
+        if black >= white {
+            return Err(format!(
+                "The black point ({black}) must be below the white point ({white})"
+            ));
+        }
+        if !(gamma > 0.0 && gamma.is_finite()) {
+            return Err(format!("Gamma must be above 0, got {gamma}"));
+        }
+
+        Ok(Self {
+            black: black / 255.0,
+            white: white / 255.0,
+            gamma,
+            out_black: out_black / 255.0,
+            out_white: out_white / 255.0,
+        })
+    }
+
+    /// Map one channel value.
+    pub(crate) fn map(&self, value: f32) -> f32 {
+        let value = ((value - self.black) / (self.white - self.black)).clamp(0.0, 1.0);
+        let value = value.powf(1.0 / self.gamma);
+        self.out_black + value * (self.out_white - self.out_black)
+    }
+
+    /// Adjust the levels of an already decoded image.
+    pub(crate) fn apply(&self, image: &DynamicImage) -> DynamicImage {
+        let mut image = image.clone();
+        map_channels(&mut image, |_, value| self.map(value));
+        image
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use image::{Rgb, RgbImage};
+
+    use super::*;
+
+    #[test]
+    fn test_levels() {
+        let test_image_data = include_bytes!("../../sample.jpg").to_vec();
+        let levels_bytes = levels(test_image_data, 10.0, 200.0, 1.2, 0.0, 255.0).unwrap();
+
+        let levels_image = image::load_from_memory(&levels_bytes).unwrap();
+
+        levels_image.save("test-output/levels.jpg").unwrap();
+    }
+
+    #[test]
+    fn test_levels_points() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_fn(256, 1, |x, _| Rgb([x as u8; 3])));
+
+        let stretched = Levels::new(50.0, 150.0, 1.0, 0.0, 255.0)
+            .unwrap()
+            .apply(&image)
+            .to_rgb8();
+        assert_eq!(stretched.get_pixel(50, 0)[0], 0);
+        assert!(stretched.get_pixel(100, 0)[0].abs_diff(128) <= 1);
+        assert_eq!(stretched.get_pixel(150, 0)[0], 255);
+        assert_eq!(stretched.get_pixel(20, 0)[0], 0);
+
+        let brighter = Levels::new(0.0, 255.0, 2.0, 0.0, 255.0)
+            .unwrap()
+            .apply(&image)
+            .to_rgb8();
+        assert_eq!(brighter.get_pixel(64, 0)[0], 128);
+
+        let faded = Levels::new(0.0, 255.0, 1.0, 40.0, 200.0)
+            .unwrap()
+            .apply(&image)
+            .to_rgb8();
+        assert_eq!(faded.get_pixel(0, 0)[0], 40);
+        assert_eq!(faded.get_pixel(255, 0)[0], 200);
+
+        assert!(Levels::new(200.0, 100.0, 1.0, 0.0, 255.0).is_err());
+        assert!(Levels::new(0.0, 255.0, 0.0, 0.0, 255.0).is_err());
+    }
+}
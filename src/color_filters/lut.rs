@@ -0,0 +1,372 @@
+use std::io::Cursor;
+
+use image::{ColorType, DynamicImage};
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use crate::utils::pixels::{common_color, convert, map_rgba};
+use crate::utils::read_image::read_image;
+
+/// Largest 3D LUT accepted, 256 points per side is far above what tools export.
+const MAX_CUBE_SIZE: usize = 256;
+
+/// Color grade the image with a LUT.
+/// `lut_bytes` is either a `.cube` file (1D or 3D) or a HaldCLUT PNG.
+/// `intensity` blends between the original (0.0) and the full grade (1.0).
+/// 3D LUTs are sampled with tetrahedral interpolation.
+///
+/// ```javascript
+/// const lut = new Uint8Array(await (await fetch("/looks/teal-orange.cube")).arrayBuffer());
+/// const graded = apply_lut(bytes, lut, 0.8);
+/// ```
+#[wasm_bindgen]
+pub fn apply_lut(
+    image_data: Vec<u8>,
+    lut_bytes: Vec<u8>,
+    intensity: f32,
+) -> Result<Vec<u8>, JsValue> {
+    let lut = Lut::parse(&lut_bytes).map_err(|err| JsValue::from_str(&err))?;
+
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    let mut buf = Vec::new();
+
+    lut.apply(&image, intensity)
+        .write_to(&mut Cursor::new(&mut buf), format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to apply LUT: {err}")))?;
+
+    Ok(buf)
+}
+
+/// A parsed color lookup table, optionally with a 1D shaper before the 3D cube.
+#[derive(Debug, Clone)]
+pub(crate) struct Lut {
+    domain_min: [f32; 3],
+    domain_max: [f32; 3],
+    shaper: Option<Vec<[f32; 3]>>,
+    cube: Option<Cube>,
+}
+
+/// A 3D table of `size`³ colors, red changing fastest.
+#[derive(Debug, Clone)]
+struct Cube {
+    size: usize,
+    table: Vec<[f32; 3]>,
+}
+
+impl Lut {
+    /// Parse `.cube` text or a HaldCLUT image, told apart by the image signature.
+    pub(crate) fn parse(bytes: &[u8]) -> Result<Self, String> {
+        if image::guess_format(bytes).is_ok() {
+            Self::from_hald(bytes)
+        } else {
+            let text = std::str::from_utf8(bytes)
+                .map_err(|_| "The LUT is neither an image nor a text .cube file".to_string())?;
+            Self::from_cube(text)
+        }
+    }
+
+    /// Parse an Adobe/Resolve `.cube` file.
+    pub(crate) fn from_cube(text: &str) -> Result<Self, String> {
+        let mut size_1d = None;
+        let mut size_3d = None;
+        let mut domain_min = [0.0; 3];
+        let mut domain_max = [1.0; 3];
+        let mut values = Vec::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || format!("Invalid .cube line {}: {line}", number + 1);
+            let mut words = line.split_whitespace();
+            let keyword = words.next().unwrap_or_default();
+
+            match keyword {
+                "TITLE" => {}
+                "LUT_1D_SIZE" | "LUT_3D_SIZE" => {
+                    let size: usize = words
+                        .next()
+                        .and_then(|w| w.parse().ok())
+                        .ok_or_else(invalid)?;
+                    if keyword == "LUT_1D_SIZE" {
+                        size_1d = Some(size);
+                    } else {
+                        size_3d = Some(size);
+                    }
+                }
+                "DOMAIN_MIN" | "DOMAIN_MAX" => {
+                    let domain = parse_triple(words).ok_or_else(invalid)?;
+                    if keyword == "DOMAIN_MIN" {
+                        domain_min = domain;
+                    } else {
+                        domain_max = domain;
+                    }
+                }
+                // Other keywords, like LUT_1D_INPUT_RANGE, are not needed.
+                keyword if keyword.starts_with(|c: char| c.is_ascii_alphabetic()) => {}
+                _ => values.push(parse_triple(line.split_whitespace()).ok_or_else(invalid)?),
+            }
+        }
+
+        if size_1d.is_none() && size_3d.is_none() {
+            return Err("The .cube file has no LUT_1D_SIZE or LUT_3D_SIZE".to_string());
+        }
+        if size_1d.is_some_and(|size| size < 2)
+            || size_3d.is_some_and(|size| !(2..=MAX_CUBE_SIZE).contains(&size))
+        {
+            return Err("The .cube LUT size is out of range".to_string());
+        }
+        let expected = size_1d
+            .unwrap_or(0)
+            .checked_add(size_3d.map_or(0, |size| size.pow(3)))
+            .ok_or_else(|| "The .cube LUT size is out of range".to_string())?;
+        if values.len() != expected {
+            return Err(format!(
+                "The .cube file has {} entries, expected {expected}",
+                values.len()
+            ));
+        }
+        if (0..3).any(|c| domain_max[c] <= domain_min[c]) {
+            return Err("DOMAIN_MAX must be above DOMAIN_MIN".to_string());
+        }
+
+        let cube_values = values.split_off(size_1d.unwrap_or(0));
+        Ok(Self {
+            domain_min,
+            domain_max,
+            shaper: size_1d.map(|_| values),
+            cube: size_3d.map(|size| Cube {
+                size,
+                table: cube_values,
+            }),
+        })
+    }
+
+    /// Read a HaldCLUT image: a square of `level³` pixels per side holding a
+    /// cube of `level²` points per side, red changing fastest.
+    pub(crate) fn from_hald(bytes: &[u8]) -> Result<Self, String> {
+        let image = read_image(bytes).map_err(|err| format!("Failed to read HaldCLUT: {err}"))?;
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let level = (2..=16usize)
+            .find(|level| level.pow(3) == width)
+            .filter(|_| width == height)
+            .ok_or_else(|| {
+                format!("A HaldCLUT image must be square with a cubed side, got {width}x{height}")
+            })?;
+
+        let table = image.to_rgb32f().pixels().map(|pixel| pixel.0).collect();
+
+        Ok(Self {
+            domain_min: [0.0; 3],
+            domain_max: [1.0; 3],
+            shaper: None,
+            cube: Some(Cube {
+                size: level * level,
+                table,
+            }),
+        })
+    }
+
+    /// Grade an already decoded image, blending with the original by `intensity`.
+    pub(crate) fn apply(&self, image: &DynamicImage, intensity: f32) -> DynamicImage {
+        let intensity = intensity.clamp(0.0, 1.0);
+        let color = common_color(image.color(), ColorType::Rgb8);
+        let mut image = convert(image.clone(), color);
+
+        map_rgba(&mut image, |[r, g, b, a]| {
+            let graded = self.lookup([r, g, b]);
+            let mix = |original: f32, graded: f32| original + (graded - original) * intensity;
+            [mix(r, graded[0]), mix(g, graded[1]), mix(b, graded[2]), a]
+        });
+
+        image
+    }
+
+    fn lookup(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let mut rgb: [f32; 3] = std::array::from_fn(|c| {
+            ((rgb[c] - self.domain_min[c]) / (self.domain_max[c] - self.domain_min[c]))
+                .clamp(0.0, 1.0)
+        });
+
+        if let Some(shaper) = &self.shaper {
+            for (channel, value) in rgb.iter_mut().enumerate() {
+                let position = *value * (shaper.len() - 1) as f32;
+                let index = (position as usize).min(shaper.len() - 2);
+                let fraction = position - index as f32;
+                let (low, high) = (shaper[index][channel], shaper[index + 1][channel]);
+                *value = (low + (high - low) * fraction).clamp(0.0, 1.0);
+            }
+        }
+
+        match &self.cube {
+            Some(cube) => cube.tetrahedral(rgb),
+            None => rgb,
+        }
+    }
+}
+
+impl Cube {
+    fn at(&self, r: usize, g: usize, b: usize) -> [f32; 3] {
+        self.table[r + g * self.size + b * self.size * self.size]
+    }
+
+    /// Interpolate inside the tetrahedron of the lattice cell holding `rgb`,
+    /// which keeps neutral grays neutral, unlike trilinear interpolation.
+    fn tetrahedral(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let scale = (self.size - 1) as f32;
+        let [(r, fr), (g, fg), (b, fb)] = rgb.map(|value| {
+            let position = value * scale;
+            let index = (position as usize).min(self.size - 2);
+            (index, position - index as f32)
+        });
+
+        let c000 = self.at(r, g, b);
+        let c111 = self.at(r + 1, g + 1, b + 1);
+        let (weights, corners) = if fr > fg {
+            if fg > fb {
+                (
+                    [1.0 - fr, fr - fg, fg - fb, fb],
+                    [c000, self.at(r + 1, g, b), self.at(r + 1, g + 1, b), c111],
+                )
+            } else if fr > fb {
+                (
+                    [1.0 - fr, fr - fb, fb - fg, fg],
+                    [c000, self.at(r + 1, g, b), self.at(r + 1, g, b + 1), c111],
+                )
+            } else {
+                (
+                    [1.0 - fb, fb - fr, fr - fg, fg],
+                    [c000, self.at(r, g, b + 1), self.at(r + 1, g, b + 1), c111],
+                )
+            }
+        } else if fb > fg {
+            (
+                [1.0 - fb, fb - fg, fg - fr, fr],
+                [c000, self.at(r, g, b + 1), self.at(r, g + 1, b + 1), c111],
+            )
+        } else if fb > fr {
+            (
+                [1.0 - fg, fg - fb, fb - fr, fr],
+                [c000, self.at(r, g + 1, b), self.at(r, g + 1, b + 1), c111],
+            )
+        } else {
+            (
+                [1.0 - fg, fg - fr, fr - fb, fb],
+                [c000, self.at(r, g + 1, b), self.at(r + 1, g + 1, b), c111],
+            )
+        };
+
+        std::array::from_fn(|channel| {
+            weights
+                .iter()
+                .zip(&corners)
+                .map(|(weight, corner)| weight * corner[channel])
+                .sum()
+        })
+    }
+}
+
+fn parse_triple<'a>(mut words: impl Iterator<Item = &'a str>) -> Option<[f32; 3]> {
+    let triple = [
+        words.next()?.parse().ok()?,
+        words.next()?.parse().ok()?,
+        words.next()?.parse().ok()?,
+    ];
+    words.next().is_none().then_some(triple)
+}
+
+#[cfg(test)]
+mod test {
+    use std::fmt::Write;
+
+    use image::{Rgb, RgbImage};
+
+    use super::*;
+
+    /// A 3D `.cube` built from a color function.
+    fn cube(size: usize, grade: impl Fn([f32; 3]) -> [f32; 3]) -> String {
+        let mut text = format!("TITLE \"test\"\n# generated\nLUT_3D_SIZE {size}\n");
+        let step = |i: usize| i as f32 / (size - 1) as f32;
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    let [r, g, b] = grade([step(r), step(g), step(b)]);
+                    writeln!(text, "{r} {g} {b}").unwrap();
+                }
+            }
+        }
+        text
+    }
+
+    fn colors() -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(16, 16, |x, y| {
+            Rgb([(x * 17) as u8, (y * 17) as u8, ((x * y) % 256) as u8])
+        }))
+    }
+
+    #[test]
+    fn test_apply_lut() {
+        let test_image_data = include_bytes!("../../sample.jpg").to_vec();
+        let warm = cube(17, |[r, g, b]| [r.powf(0.8), g, b * 0.85]);
+        let graded_bytes = apply_lut(test_image_data, warm.into_bytes(), 1.0).unwrap();
+
+        let graded_image = image::load_from_memory(&graded_bytes).unwrap();
+
+        graded_image.save("test-output/lut.jpg").unwrap();
+    }
+
+    #[test]
+    fn test_identity_cube() {
+        let lut = Lut::from_cube(&cube(5, |rgb| rgb)).unwrap();
+        let image = colors();
+
+        assert_eq!(lut.apply(&image, 1.0).to_rgb8(), image.to_rgb8());
+    }
+
+    #[test]
+    fn test_1d_and_intensity() {
+        let lut = Lut::from_cube("LUT_1D_SIZE 2\n1 1 1\n0 0 0\n").unwrap();
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, Rgb([0, 100, 255])));
+
+        assert_eq!(
+            *lut.apply(&image, 1.0).to_rgb8().get_pixel(0, 0),
+            Rgb([255, 155, 0])
+        );
+        assert_eq!(
+            *lut.apply(&image, 0.5).to_rgb8().get_pixel(0, 0),
+            Rgb([128, 128, 128])
+        );
+    }
+
+    #[test]
+    fn test_hald_identity() {
+        // Level 2: an 8x8 image holding a 4x4x4 cube.
+        let hald = RgbImage::from_fn(8, 8, |x, y| {
+            let index = y * 8 + x;
+            let step = |i: u32| (i * 255 / 3) as u8;
+            Rgb([step(index % 4), step(index / 4 % 4), step(index / 16)])
+        });
+        let mut png = Vec::new();
+        hald.write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+
+        let lut = Lut::parse(&png).unwrap();
+        let image = colors();
+        assert_eq!(lut.apply(&image, 1.0).to_rgb8(), image.to_rgb8());
+    }
+
+    #[test]
+    fn test_invalid_cube() {
+        assert!(Lut::from_cube("LUT_3D_SIZE 2\n0 0 0\n").is_err());
+        assert!(Lut::from_cube("LUT_3D_SIZE 3000000\n0 0 0\n").is_err());
+        assert!(Lut::from_cube(&format!("LUT_1D_SIZE {}\n0 0 0\n", usize::MAX)).is_err());
+        assert!(Lut::from_cube("0 0 0\n").is_err());
+        assert!(Lut::from_cube("LUT_1D_SIZE 2\n0 0\n1 1 1\n").is_err());
+        assert!(Lut::parse(&[0xff, 0xfe, 0x00]).is_err());
+    }
+}
@@ -3,5 +3,31 @@ pub mod brighten;
 pub mod contrast;
 pub mod fast_blur;
 pub mod grayscale;
+pub mod edge_detection;
 pub mod invert;
 pub mod hue_rotate;
+pub mod pixelate;
+pub mod redact;
+pub mod sharpen;
+pub mod denoise;
+pub mod convolve;
+pub mod sepia;
+pub mod saturate;
+pub mod opacity;
+pub mod drop_shadow;
+pub mod css_filter;
+pub mod color_matrix;
+pub mod lut;
+pub mod vignette;
+pub mod grain;
+pub mod levels;
+pub mod curves;
+pub mod auto_levels;
+pub mod equalize;
+pub mod white_balance;
+pub mod hsl;
+pub mod tone;
+pub mod bit_depth;
+pub mod quantize;
+pub mod dither;
+pub mod threshold;
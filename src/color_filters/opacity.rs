@@ -0,0 +1,41 @@
+use image::DynamicImage;
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use super::color_matrix::ColorMatrix;
+use crate::utils::read_image::read_image;
+use crate::utils::write_image::write_image;
+
+/// Make the image see-through, like CSS `opacity()`.
+/// `amount` of 0 is fully transparent and 1.0 (100%) does nothing.
+/// Formats without an alpha channel, like JPEG, keep the colors only.
+#[wasm_bindgen]
+pub fn opacity(image_data: Vec<u8>, amount: f32) -> Result<Vec<u8>, JsValue> {
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    write_image(&apply(&image, amount), format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to change opacity: {err}")))
+}
+
+/// Scale the alpha of an already decoded image.
+pub(crate) fn apply(image: &DynamicImage, amount: f32) -> DynamicImage {
+    ColorMatrix::opacity(amount).apply(image)
+}
+
+#[cfg(test)]
+mod test {
+    use image::{Rgb, RgbImage};
+
+    use super::*;
+
+    #[test]
+    fn test_opacity_adds_alpha() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 2, Rgb([10, 20, 30])));
+        let faded = apply(&image, 0.5).to_rgba8();
+
+        assert_eq!(faded.get_pixel(1, 1).0, [10, 20, 30, 128]);
+    }
+}
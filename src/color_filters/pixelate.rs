@@ -0,0 +1,111 @@
+use std::io::Cursor;
+
+use image::{DynamicImage, ImageBuffer, Pixel};
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use crate::utils::pixels::{from_unit, map_buffer, to_unit};
+use crate::utils::read_image::read_image;
+
+/// Pixelate the image into square blocks of `block_size` pixels.
+/// Every pixel of a block is replaced by the average color of the block,
+/// so unlike a blur no detail inside the block survives.
+/// Use it with a region on `ImageProcessor` to redact faces or plates.
+#[wasm_bindgen]
+pub fn pixelate(image_data: Vec<u8>, block_size: u32) -> Result<Vec<u8>, JsValue> {
+    if block_size == 0 {
+        return Err(JsValue::from_str("The block size must be at least 1"));
+    }
+
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    let mut buf = Vec::new();
+
+    apply(&image, block_size)
+        .write_to(&mut Cursor::new(&mut buf), format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to pixelate the image: {err}")))?;
+
+    Ok(buf)
+}
+
+/// Pixelate an already decoded image.
+pub(crate) fn apply(image: &DynamicImage, block_size: u32) -> DynamicImage {
+    map_buffer!(image, buffer => pixelate_buffer(buffer, block_size.max(1)))
+}
+
+fn pixelate_buffer<P: Pixel>(
+    buffer: &ImageBuffer<P, Vec<P::Subpixel>>,
+    block_size: u32,
+) -> ImageBuffer<P, Vec<P::Subpixel>> {
+    let (width, height) = buffer.dimensions();
+    let channels = P::CHANNEL_COUNT as usize;
+    let mut out = buffer.clone();
+
+    for block_y in (0..height).step_by(block_size as usize) {
+        for block_x in (0..width).step_by(block_size as usize) {
+            let block_width = block_size.min(width - block_x);
+            let block_height = block_size.min(height - block_y);
+
+            let mut sum = [0f64; 4];
+            for y in block_y..block_y + block_height {
+                for x in block_x..block_x + block_width {
+                    for (total, &value) in sum.iter_mut().zip(buffer.get_pixel(x, y).channels()) {
+                        *total += to_unit(value) as f64;
+                    }
+                }
+            }
+
+            let count = (block_width * block_height) as f64;
+            let mut average = *buffer.get_pixel(block_x, block_y);
+            for (value, total) in average.channels_mut().iter_mut().zip(&sum[..channels]) {
+                *value = from_unit((total / count) as f32);
+            }
+
+            for y in block_y..block_y + block_height {
+                for x in block_x..block_x + block_width {
+                    out.put_pixel(x, y, average);
+                }
+            }
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use image::{Rgb, RgbImage};
+
+    use super::*;
+
+    #[test]
+    fn test_pixelate() {
+        let test_image_data = include_bytes!("../../sample.jpg").to_vec();
+        let pixelated_bytes = pixelate(test_image_data, 32).unwrap();
+
+        let pixelated_image = image::load_from_memory(&pixelated_bytes).unwrap();
+
+        pixelated_image.save("test-output/pixelate_32.jpg").unwrap();
+    }
+
+    #[test]
+    fn test_blocks_are_fully_replaced() {
+        let gradient = RgbImage::from_fn(10, 7, |x, y| Rgb([(x * 20) as u8, (y * 30) as u8, 77]));
+        let pixelated = apply(&DynamicImage::ImageRgb8(gradient), 4).to_rgb8();
+
+        // Blocks at the right and bottom edges are smaller than the block size.
+        for (x0, y0, x1, y1) in [(0, 0, 4, 4), (8, 0, 10, 4), (4, 4, 8, 7), (8, 4, 10, 7)] {
+            let block: Vec<_> = (y0..y1)
+                .flat_map(|y| (x0..x1).map(move |x| (x, y)))
+                .map(|(x, y)| *pixelated.get_pixel(x, y))
+                .collect();
+
+            let expected_red = (x0..x1).map(|x| x * 20).sum::<u32>() as f32 / (x1 - x0) as f32;
+            assert!(block.iter().all(|pixel| *pixel == block[0]));
+            assert_eq!(block[0][0], expected_red.round() as u8);
+        }
+    }
+}
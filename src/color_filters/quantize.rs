@@ -0,0 +1,236 @@
+use color_quant::NeuQuant;
+use image::{DynamicImage, ImageFormat};
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use super::dither::{self, Dither};
+use crate::utils::indexed::Indexed;
+use crate::utils::read_image::read_image;
+use crate::utils::write_image::write_image;
+
+/// Speed of the NeuQuant training, 1 is best and 30 fastest. 10 is its recommended default.
+const SAMPLE_FACTOR: i32 = 10;
+
+/// Reduce the image to at most `colors` colors (2 to 256), the biggest size win
+/// for PNG: PNGs come back as indexed color files, often a third of the size.
+/// `dither` hides banding in gradients by mixing nearby palette colors.
+/// Images that already have few enough colors keep them exactly.
+#[wasm_bindgen]
+pub fn quantize(image_data: Vec<u8>, colors: u32, dither: bool) -> Result<Vec<u8>, JsValue> {
+    let method = if dither {
+        Dither::FloydSteinberg
+    } else {
+        Dither::None
+    };
+    let quantizer = Quantizer::new(colors, method).map_err(|err| JsValue::from_str(&err))?;
+
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    quantizer
+        .encode(&image, format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to quantize the image: {err}")))
+}
+
+/// Encode an already decoded image as an indexed PNG, with its own colors when
+/// it has no more than 256 and a computed 256 color palette otherwise.
+pub(crate) fn write_indexed_png(image: &DynamicImage) -> Result<Vec<u8>, String> {
+    Quantizer {
+        palette: Palette::Computed(256),
+        dither: Dither::None,
+    }
+    .indexed(image)
+    .to_png()
+    .map_err(|err| err.to_string())
+}
+
+/// Where the colors of a `Quantizer` come from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Palette {
+    /// Up to this many colors picked for each image.
+    Computed(usize),
+    /// These opaque colors, whatever the image.
+    Fixed(Vec<[u8; 4]>),
+}
+
+/// Settings for reducing an image to a palette.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Quantizer {
+    palette: Palette,
+    dither: Dither,
+}
+
+impl Quantizer {
+    /// Reduce to a palette of up to `colors` colors computed from each image.
+    pub(crate) fn new(colors: u32, dither: Dither) -> Result<Self, String> {
+        if !(2..=256).contains(&colors) {
+            return Err(format!("The color count must be 2 to 256, got {colors}"));
+        }
+        Ok(Self {
+            palette: Palette::Computed(colors as usize),
+            dither,
+        })
+    }
+
+    /// Reduce to a fixed palette given as RGB byte triples.
+    pub(crate) fn fixed(palette: &[u8], dither: Dither) -> Result<Self, String> {
+        if !palette.len().is_multiple_of(3) || !(2..=256).contains(&(palette.len() / 3)) {
+            return Err(format!(
+                "The palette must be 2 to 256 RGB colors, got {} bytes",
+                palette.len()
+            ));
+        }
+        let colors = palette
+            .chunks_exact(3)
+            .map(|color| [color[0], color[1], color[2], u8::MAX])
+            .collect();
+        Ok(Self {
+            palette: Palette::Fixed(colors),
+            dither,
+        })
+    }
+
+    /// Reduce an already decoded image to a palette.
+    pub(crate) fn indexed(&self, image: &DynamicImage) -> Indexed {
+        match &self.palette {
+            Palette::Computed(colors) => self.computed(image, *colors),
+            Palette::Fixed(colors) => self.fixed_colors(image, colors),
+        }
+    }
+
+    /// Reduce an already decoded image to a palette, keeping it as pixels.
+    pub(crate) fn apply(&self, image: &DynamicImage) -> DynamicImage {
+        self.indexed(image).to_image()
+    }
+
+    /// Reduce and encode an already decoded image, PNGs as indexed color files.
+    pub(crate) fn encode(
+        &self,
+        image: &DynamicImage,
+        format: ImageFormat,
+    ) -> Result<Vec<u8>, String> {
+        let indexed = self.indexed(image);
+        if format == ImageFormat::Png {
+            indexed.to_png().map_err(|err| err.to_string())
+        } else {
+            write_image(&indexed.to_image(), format).map_err(|err| err.to_string())
+        }
+    }
+
+    fn computed(&self, image: &DynamicImage, colors: usize) -> Indexed {
+        if let Some(indexed) = Indexed::exact(image, colors) {
+            return indexed;
+        }
+
+        let mut rgba = image.to_rgba8();
+        // Transparent pixels have no color worth a palette entry.
+        for pixel in rgba.pixels_mut().filter(|pixel| pixel[3] == 0) {
+            pixel.0 = [0; 4];
+        }
+
+        let quant = NeuQuant::new(SAMPLE_FACTOR, colors, rgba.as_raw());
+        let palette: Vec<[u8; 4]> = quant
+            .color_map_rgba()
+            .chunks_exact(4)
+            .map(|color| [color[0], color[1], color[2], color[3]])
+            .collect();
+
+        let indices = dither::indices(&rgba, self.dither, &palette, |color| {
+            quant.index_of(&color) as u8
+        });
+        Indexed::new(rgba.width(), rgba.height(), palette, indices)
+    }
+
+    /// Map to a fixed palette. Pixels less than half opaque become transparent
+    /// when there is room for one more palette color.
+    fn fixed_colors(&self, image: &DynamicImage, colors: &[[u8; 4]]) -> Indexed {
+        let mut rgba = image.to_rgba8();
+        let mut palette = colors.to_vec();
+        let transparent =
+            (palette.len() < 256 && rgba.pixels().any(|pixel| pixel[3] < 128)).then(|| {
+                palette.push([0; 4]);
+                palette.len() as u8 - 1
+            });
+        for pixel in rgba.pixels_mut() {
+            pixel[3] = if transparent.is_some() && pixel[3] < 128 {
+                0
+            } else {
+                u8::MAX
+            };
+        }
+
+        let indices = dither::indices(&rgba, self.dither, &palette, |[r, g, b, a]| {
+            if let Some(index) = transparent.filter(|_| a == 0) {
+                return index;
+            }
+            let distance = |color: &[u8; 4]| {
+                (0..3)
+                    .map(|c| (color[c] as i32 - [r, g, b][c] as i32).pow(2))
+                    .sum::<i32>()
+            };
+            (0..colors.len())
+                .min_by_key(|&index| distance(&colors[index]))
+                .unwrap_or(0) as u8
+        });
+        Indexed::new(rgba.width(), rgba.height(), palette, indices)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use image::{Rgb, RgbImage};
+
+    use super::*;
+
+    #[test]
+    fn test_quantize() {
+        let test_image_data = include_bytes!("../../sample.jpg").to_vec();
+        let quantized_bytes = quantize(test_image_data, 16, true).unwrap();
+
+        let quantized_image = image::load_from_memory(&quantized_bytes).unwrap();
+
+        quantized_image.save("test-output/quantize.jpg").unwrap();
+    }
+
+    #[test]
+    fn test_quantize_to_indexed_png() {
+        let photo = image::load_from_memory(include_bytes!("../../sample.jpg")).unwrap();
+        let photo = photo.thumbnail(256, 256);
+        let png = write_image(&photo, ImageFormat::Png).unwrap();
+
+        let quantized = quantize(png.clone(), 64, false).unwrap();
+        assert!(quantized.len() < png.len() / 2);
+
+        let decoded = image::load_from_memory(&quantized).unwrap().to_rgb8();
+        let mut colors: Vec<_> = decoded.pixels().map(|pixel| pixel.0).collect();
+        colors.sort_unstable();
+        colors.dedup();
+        assert!(colors.len() <= 64);
+
+        assert!(Quantizer::new(1, Dither::None).is_err());
+        assert!(Quantizer::new(257, Dither::None).is_err());
+    }
+
+    #[test]
+    fn test_dithering_keeps_the_average() {
+        // A smooth ramp squeezed into 4 colors bands without dithering.
+        let ramp =
+            DynamicImage::ImageRgb8(RgbImage::from_fn(128, 32, |x, _| Rgb([x as u8 * 2; 3])));
+        let quantizer = Quantizer::new(4, Dither::FloydSteinberg).unwrap();
+        let dithered = quantizer.apply(&ramp).to_rgb8();
+
+        for x in [20, 50, 90] {
+            let mean = (0..32)
+                .map(|y| dithered.get_pixel(x, y)[0] as f32)
+                .sum::<f32>()
+                / 32.0;
+            let wanted = (x * 2) as f32;
+            assert!(
+                (mean - wanted).abs() < 16.0,
+                "column {x}: {mean} for {wanted}"
+            );
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use std::io::Cursor;
+
+use image::{ColorType, DynamicImage, ImageBuffer, Pixel};
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use crate::utils::pixels::{common_color, convert, from_unit, to_unit, with_buffer};
+use crate::utils::read_image::read_image;
+
+/// Cover a rectangle of the image with a solid, opaque color.
+/// `r`, `g` and `b` are the color channels from 0 to 255.
+/// The pixels inside the box are overwritten, nothing of the original remains.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn redact(
+    image_data: Vec<u8>,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    r: u8,
+    g: u8,
+    b: u8,
+) -> Result<Vec<u8>, JsValue> {
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    let mut buf = Vec::new();
+
+    apply(image, x, y, width, height, [r, g, b])
+        .write_to(&mut Cursor::new(&mut buf), format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to redact the image: {err}")))?;
+
+    Ok(buf)
+}
+
+/// Cover a rectangle of an already decoded image with a solid color.
+/// Grayscale images are converted to color unless the color is a gray.
+pub(crate) fn apply(
+    image: DynamicImage,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    color: [u8; 3],
+) -> DynamicImage {
+    let [r, g, b] = color;
+    let fill_color = if r == g && g == b {
+        ColorType::L8
+    } else {
+        ColorType::Rgb8
+    };
+    let color_type = common_color(image.color(), fill_color);
+
+    let mut image = convert(image, color_type);
+    with_buffer!(&mut image, buffer => fill(buffer, x, y, width, height, color));
+    image
+}
+
+fn fill<P: Pixel>(
+    buffer: &mut ImageBuffer<P, Vec<P::Subpixel>>,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    color: [u8; 3],
+) {
+    if buffer.width() == 0 || buffer.height() == 0 {
+        return;
+    }
+
+    let x_end = x.saturating_add(width).min(buffer.width());
+    let y_end = y.saturating_add(height).min(buffer.height());
+
+    let color = color.map(to_unit);
+    let values: &[f32] = match P::CHANNEL_COUNT {
+        1 => &[color[0]],
+        2 => &[color[0], 1.0],
+        3 => &color,
+        _ => &[color[0], color[1], color[2], 1.0],
+    };
+    let mut pixel = *buffer.get_pixel(0, 0);
+    for (channel, &value) in pixel.channels_mut().iter_mut().zip(values) {
+        *channel = from_unit(value);
+    }
+
+    for py in y.min(y_end)..y_end {
+        for px in x.min(x_end)..x_end {
+            buffer.put_pixel(px, py, pixel);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use image::{GenericImageView, Rgba, RgbaImage};
+
+    use super::*;
+
+    #[test]
+    fn test_redact() {
+        let test_image_data = include_bytes!("../../sample.jpg").to_vec();
+        let redacted_bytes = redact(test_image_data, 100, 100, 400, 200, 0, 0, 0).unwrap();
+
+        let redacted_image = image::load_from_memory(&redacted_bytes).unwrap();
+
+        redacted_image.save("test-output/redact.jpg").unwrap();
+    }
+
+    #[test]
+    fn test_box_is_fully_replaced() {
+        let noise = RgbaImage::from_fn(12, 12, |x, y| Rgba([(x * 21) as u8, (y * 19) as u8, 5, 9]));
+        let redacted = apply(
+            DynamicImage::ImageRgba8(noise.clone()),
+            2,
+            3,
+            6,
+            20,
+            [10, 20, 30],
+        );
+
+        for (x, y, pixel) in redacted.pixels() {
+            if (2..8).contains(&x) && y >= 3 {
+                assert_eq!(pixel, Rgba([10, 20, 30, 255]));
+            } else {
+                assert_eq!(pixel, *noise.get_pixel(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn test_redact_empty_image() {
+        let empty = DynamicImage::ImageRgba8(RgbaImage::new(0, 0));
+        let redacted = apply(empty, 0, 0, 10, 10, [10, 20, 30]);
+        assert_eq!(redacted.dimensions(), (0, 0));
+    }
+}
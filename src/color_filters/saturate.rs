@@ -0,0 +1,57 @@
+use std::io::Cursor;
+
+use image::DynamicImage;
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use super::color_matrix::ColorMatrix;
+use crate::utils::read_image::read_image;
+
+/// Change the color saturation, like CSS `saturate()`.
+/// `amount` of 0 is fully gray, 1.0 (100%) does nothing and larger values boost colors.
+#[wasm_bindgen]
+pub fn saturate(image_data: Vec<u8>, amount: f32) -> Result<Vec<u8>, JsValue> {
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    let mut buf = Vec::new();
+
+    apply(&image, amount)
+        .write_to(&mut Cursor::new(&mut buf), format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to saturate: {err}")))?;
+
+    Ok(buf)
+}
+
+/// Saturate an already decoded image.
+pub(crate) fn apply(image: &DynamicImage, amount: f32) -> DynamicImage {
+    ColorMatrix::saturate(amount).apply(image)
+}
+
+#[cfg(test)]
+mod test {
+    use image::{Rgb, RgbImage};
+
+    use super::*;
+
+    #[test]
+    fn test_saturate() {
+        let test_image_data = include_bytes!("../../sample.jpg").to_vec();
+        let saturated_bytes = saturate(test_image_data, 1.4).unwrap();
+
+        let saturated_image = image::load_from_memory(&saturated_bytes).unwrap();
+
+        saturated_image.save("test-output/saturate.jpg").unwrap();
+    }
+
+    #[test]
+    fn test_saturate_zero_is_gray() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, Rgb([200, 40, 90])));
+        let pixel = *apply(&image, 0.0).to_rgb8().get_pixel(0, 0);
+
+        assert_eq!(pixel[0], pixel[1]);
+        assert_eq!(pixel[1], pixel[2]);
+    }
+}
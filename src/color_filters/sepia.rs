@@ -0,0 +1,63 @@
+use std::io::Cursor;
+
+use image::DynamicImage;
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use super::color_matrix::ColorMatrix;
+use crate::utils::read_image::read_image;
+
+/// Give the image a warm, old photo tone, like CSS `sepia()`.
+/// `amount` of 0 does nothing and 1.0 (100%) is full sepia, larger values are treated as 1.0.
+#[wasm_bindgen]
+pub fn sepia(image_data: Vec<u8>, amount: f32) -> Result<Vec<u8>, JsValue> {
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    let mut buf = Vec::new();
+
+    apply(&image, amount)
+        .write_to(&mut Cursor::new(&mut buf), format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to apply sepia: {err}")))?;
+
+    Ok(buf)
+}
+
+/// Sepia tone an already decoded image.
+pub(crate) fn apply(image: &DynamicImage, amount: f32) -> DynamicImage {
+    ColorMatrix::sepia(amount).apply(image)
+}
+
+#[cfg(test)]
+mod test {
+    use image::{Rgb, RgbImage};
+
+    use super::*;
+
+    #[test]
+    fn test_sepia() {
+        let test_image_data = include_bytes!("../../sample.jpg").to_vec();
+        let sepia_bytes = sepia(test_image_data, 0.8).unwrap();
+
+        let sepia_image = image::load_from_memory(&sepia_bytes).unwrap();
+
+        sepia_image.save("test-output/sepia.jpg").unwrap();
+    }
+
+    #[test]
+    fn test_sepia_matches_css() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, Rgb([100, 150, 200])));
+
+        assert_eq!(
+            *apply(&image, 0.0).to_rgb8().get_pixel(0, 0),
+            Rgb([100, 150, 200])
+        );
+        // 0.393 * 100 + 0.769 * 150 + 0.189 * 200 = 192.45, and so on.
+        assert_eq!(
+            *apply(&image, 1.0).to_rgb8().get_pixel(0, 0),
+            Rgb([192, 171, 134])
+        );
+    }
+}
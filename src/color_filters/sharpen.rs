@@ -0,0 +1,138 @@
+use std::io::Cursor;
+
+use image::{DynamicImage, ImageBuffer, Pixel, imageops};
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use super::convolve::Convolution;
+use crate::utils::pixels::{color_channels, from_unit, map_buffer, to_unit};
+use crate::utils::read_image::read_image;
+
+/// Settings used by `ImageProcessor` to sharpen after a downscale.
+const MILD_SIGMA: f32 = 0.7;
+const MILD_AMOUNT: f32 = 0.5;
+const MILD_THRESHOLD: i32 = 2;
+
+/// Sharpen the image with an unsharp mask.
+/// `sigma` is the radius of the Gaussian blur used for the mask, 0.5 to 2 works for most photos.
+/// `amount` is how strongly edges are boosted, 1.0 adds the full difference.
+/// `threshold` is the smallest difference (0 to 255) that is sharpened,
+/// raise it to keep noise and smooth areas untouched.
+#[wasm_bindgen]
+pub fn unsharpen(
+    image_data: Vec<u8>,
+    sigma: f32,
+    amount: f32,
+    threshold: i32,
+) -> Result<Vec<u8>, JsValue> {
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    let mut buf = Vec::new();
+
+    apply_unsharpen(&image, sigma, amount, threshold)
+        .write_to(&mut Cursor::new(&mut buf), format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to sharpen the image: {err}")))?;
+
+    Ok(buf)
+}
+
+/// Sharpen the image with a simple 3x3 kernel.
+/// `strength` of 0 does nothing, 1.0 is a strong sharpen.
+#[wasm_bindgen]
+pub fn sharpen(image_data: Vec<u8>, strength: f32) -> Result<Vec<u8>, JsValue> {
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    let mut buf = Vec::new();
+
+    apply_sharpen(&image, strength)
+        .write_to(&mut Cursor::new(&mut buf), format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to sharpen the image: {err}")))?;
+
+    Ok(buf)
+}
+
+/// Unsharp mask an already decoded image.
+pub(crate) fn apply_unsharpen(
+    image: &DynamicImage,
+    sigma: f32,
+    amount: f32,
+    threshold: i32,
+) -> DynamicImage {
+    let threshold = threshold.max(0) as f32 / 255.0;
+    map_buffer!(image, buffer => unsharpen_buffer(buffer, sigma, amount, threshold))
+}
+
+/// Sharpen an already decoded image with the 3x3 kernel.
+pub(crate) fn apply_sharpen(image: &DynamicImage, strength: f32) -> DynamicImage {
+    let s = strength;
+    Convolution::color_3x3([0.0, -s, 0.0, -s, 1.0 + 4.0 * s, -s, 0.0, -s, 0.0]).apply(image)
+}
+
+/// The light unsharp mask applied after downscaling, when enabled.
+pub(crate) fn apply_mild(image: &DynamicImage) -> DynamicImage {
+    apply_unsharpen(image, MILD_SIGMA, MILD_AMOUNT, MILD_THRESHOLD)
+}
+
+fn unsharpen_buffer<P: Pixel + 'static>(
+    buffer: &ImageBuffer<P, Vec<P::Subpixel>>,
+    sigma: f32,
+    amount: f32,
+    threshold: f32,
+) -> ImageBuffer<P, Vec<P::Subpixel>> {
+    let blurred = imageops::blur(buffer, sigma);
+    let mut out = buffer.clone();
+    let color_channels = color_channels::<P>();
+
+    for (pixel, blurred) in out.pixels_mut().zip(blurred.pixels()) {
+        let pixel = &mut pixel.channels_mut()[..color_channels];
+        for (value, &blurred) in pixel.iter_mut().zip(blurred.channels()) {
+            let original = to_unit(*value);
+            let difference = original - to_unit(blurred);
+            if difference.abs() >= threshold {
+                *value = from_unit(original + difference * amount);
+            }
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use image::{GrayImage, Luma};
+
+    use super::*;
+
+    #[test]
+    fn test_unsharpen() {
+        let test_image_data = include_bytes!("../../sample.jpg").to_vec();
+        let sharpened_bytes = unsharpen(test_image_data, 1.5, 1.0, 2).unwrap();
+
+        let sharpened_image = image::load_from_memory(&sharpened_bytes).unwrap();
+
+        sharpened_image.save("test-output/unsharpen.jpg").unwrap();
+    }
+
+    #[test]
+    fn test_sharpen_boosts_edges() {
+        let edge = GrayImage::from_fn(8, 8, |x, _| Luma([if x < 4 { 100 } else { 150 }]));
+        let image = DynamicImage::ImageLuma8(edge);
+
+        for sharpened in [
+            apply_sharpen(&image, 0.5),
+            apply_unsharpen(&image, 1.0, 1.0, 0),
+        ] {
+            let sharpened = sharpened.to_luma8();
+            assert!(sharpened.get_pixel(3, 4)[0] < 100);
+            assert!(sharpened.get_pixel(4, 4)[0] > 150);
+            assert_eq!(sharpened.get_pixel(0, 4)[0], 100);
+        }
+    }
+}
@@ -0,0 +1,288 @@
+use image::{DynamicImage, ImageFormat};
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use crate::utils::indexed::Indexed;
+use crate::utils::pixels::gray_like;
+use crate::utils::read_image::read_image;
+use crate::utils::write_image::write_image;
+
+/// Dynamic range of the local standard deviation in Sauvola's formula, for values in `0.0..=1.0`.
+const SAUVOLA_RANGE: f64 = 0.5;
+
+/// Turn the image black and white: pixels brighter than `value` (0 to 255)
+/// become white, the rest black. PNGs come back as 1-bit files.
+#[wasm_bindgen]
+pub fn threshold(image_data: Vec<u8>, value: f32) -> Result<Vec<u8>, JsValue> {
+    binarize(image_data, |image| apply(image, value))
+}
+
+/// Like `threshold`, with the value picked from the image by Otsu's method,
+/// which splits the brightness histogram into its two best separated groups.
+/// Works well for evenly lit scans.
+#[wasm_bindgen]
+pub fn otsu_threshold(image_data: Vec<u8>) -> Result<Vec<u8>, JsValue> {
+    binarize(image_data, apply_otsu)
+}
+
+/// Black and white with a threshold per pixel: the mean brightness of the
+/// `window_size` pixel square around it, minus `offset` (0 to 255).
+/// Copes with shadows and uneven light on photographed paper. A window a bit
+/// larger than the text height and an offset of 10 suit most documents.
+#[wasm_bindgen]
+pub fn adaptive_threshold(
+    image_data: Vec<u8>,
+    window_size: u32,
+    offset: f32,
+) -> Result<Vec<u8>, JsValue> {
+    binarize(image_data, |image| {
+        apply_adaptive(image, window_size, offset)
+    })
+}
+
+/// Black and white with Sauvola's local threshold, which also looks at the
+/// contrast around each pixel so blank paper with stains or texture stays white.
+/// `k` of 0.2 to 0.5 sets how dark ink has to be, higher keeps less.
+#[wasm_bindgen]
+pub fn sauvola_threshold(
+    image_data: Vec<u8>,
+    window_size: u32,
+    k: f32,
+) -> Result<Vec<u8>, JsValue> {
+    binarize(image_data, |image| apply_sauvola(image, window_size, k))
+}
+
+/// Decode, binarize and encode, PNGs as 1-bit files.
+fn binarize(
+    image_data: Vec<u8>,
+    binarize: impl FnOnce(&DynamicImage) -> DynamicImage,
+) -> Result<Vec<u8>, JsValue> {
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    let binary = binarize(&image);
+    let encoded = match Indexed::exact(&binary, 2) {
+        Some(indexed) if format == ImageFormat::Png => {
+            indexed.to_png().map_err(|err| err.to_string())
+        }
+        _ => write_image(&binary, format).map_err(|err| err.to_string()),
+    };
+
+    encoded.map_err(|err| JsValue::from_str(&format!("Failed to threshold the image: {err}")))
+}
+
+/// Threshold an already decoded image at `value` (0 to 255).
+pub(crate) fn apply(image: &DynamicImage, value: f32) -> DynamicImage {
+    let level = value / 255.0;
+    map_binary(image, |_, luminance| luminance > level)
+}
+
+/// Threshold an already decoded image at its Otsu level.
+pub(crate) fn apply_otsu(image: &DynamicImage) -> DynamicImage {
+    apply(image, otsu_level(image))
+}
+
+/// Threshold an already decoded image against the mean of its surroundings.
+pub(crate) fn apply_adaptive(image: &DynamicImage, window_size: u32, offset: f32) -> DynamicImage {
+    let offset = offset as f64 / 255.0;
+    let local = LocalStats::new(image, window_size);
+    map_binary(image, |index, luminance| {
+        let (mean, _) = local.at(index);
+        luminance as f64 > mean - offset
+    })
+}
+
+/// Threshold an already decoded image with Sauvola's method.
+pub(crate) fn apply_sauvola(image: &DynamicImage, window_size: u32, k: f32) -> DynamicImage {
+    let k = k as f64;
+    let local = LocalStats::new(image, window_size);
+    map_binary(image, |index, luminance| {
+        let (mean, deviation) = local.at(index);
+        luminance as f64 > mean * (1.0 + k * (deviation / SAUVOLA_RANGE - 1.0))
+    })
+}
+
+/// The brightness, 0 to 255, that best splits the image into dark and light
+/// pixels: the one with the largest variance between the two groups.
+pub(crate) fn otsu_level(image: &DynamicImage) -> f32 {
+    let mut histogram = [0u64; 256];
+    for &value in image.to_luma32f().iter() {
+        histogram[(value.clamp(0.0, 1.0) * 255.0).round() as usize] += 1;
+    }
+
+    let total: u64 = histogram.iter().sum();
+    let sum: f64 = histogram
+        .iter()
+        .enumerate()
+        .map(|(level, &count)| level as f64 * count as f64)
+        .sum();
+
+    let (mut dark, mut dark_sum) = (0u64, 0f64);
+    let (mut best, mut best_variance) = (0, 0f64);
+    for (level, &count) in histogram.iter().enumerate() {
+        dark += count;
+        dark_sum += level as f64 * count as f64;
+        let light = total - dark;
+        if dark == 0 || light == 0 {
+            continue;
+        }
+        let dark_mean = dark_sum / dark as f64;
+        let light_mean = (sum - dark_sum) / light as f64;
+        let variance = dark as f64 * light as f64 * (dark_mean - light_mean).powi(2);
+        if variance > best_variance {
+            (best, best_variance) = (level, variance);
+        }
+    }
+    best as f32
+}
+
+/// Black and white pixels from `white`, which gets the pixel index and its
+/// luminance in `0.0..=1.0`. Keeps the bit depth of `image`.
+fn map_binary(image: &DynamicImage, white: impl Fn(usize, f32) -> bool) -> DynamicImage {
+    let luma = image.to_luma32f();
+    let values = luma
+        .iter()
+        .enumerate()
+        .map(|(index, &luminance)| if white(index, luminance) { 1.0 } else { 0.0 })
+        .collect();
+    gray_like(image.color(), luma.width(), luma.height(), values)
+}
+
+/// Mean and standard deviation of the luminance in a square around every
+/// pixel, from summed area tables so any window size costs the same.
+struct LocalStats {
+    width: usize,
+    height: usize,
+    radius: usize,
+    sums: Vec<f64>,
+    squares: Vec<f64>,
+}
+
+impl LocalStats {
+    fn new(image: &DynamicImage, window_size: u32) -> Self {
+        let luma = image.to_luma32f();
+        let (width, height) = (luma.width() as usize, luma.height() as usize);
+        let stride = width + 1;
+        let mut sums = vec![0f64; stride * (height + 1)];
+        let mut squares = vec![0f64; stride * (height + 1)];
+
+        for y in 0..height {
+            let (mut row_sum, mut row_squares) = (0f64, 0f64);
+            for x in 0..width {
+                let value = luma.as_raw()[y * width + x] as f64;
+                row_sum += value;
+                row_squares += value * value;
+                let at = (y + 1) * stride + x + 1;
+                sums[at] = sums[at - stride] + row_sum;
+                squares[at] = squares[at - stride] + row_squares;
+            }
+        }
+
+        Self {
+            width,
+            height,
+            radius: (window_size as usize / 2).max(1),
+            sums,
+            squares,
+        }
+    }
+
+    /// Mean and standard deviation around the pixel at `index`, clipped to the image.
+    fn at(&self, index: usize) -> (f64, f64) {
+        let (x, y) = (index % self.width, index / self.width);
+        let (left, top) = (x.saturating_sub(self.radius), y.saturating_sub(self.radius));
+        let right = (x + self.radius + 1).min(self.width);
+        let bottom = (y + self.radius + 1).min(self.height);
+
+        let stride = self.width + 1;
+        let area = |table: &[f64]| {
+            table[bottom * stride + right]
+                - table[top * stride + right]
+                - table[bottom * stride + left]
+                + table[top * stride + left]
+        };
+        let count = ((right - left) * (bottom - top)) as f64;
+        let mean = area(&self.sums) / count;
+        let variance = (area(&self.squares) / count - mean * mean).max(0.0);
+        (mean, variance.sqrt())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use image::{GrayImage, Luma};
+
+    use super::*;
+
+    /// Dark text strokes on paper lit from the left, fading into shadow on the right.
+    fn shaded_page() -> DynamicImage {
+        DynamicImage::ImageLuma8(GrayImage::from_fn(200, 60, |x, y| {
+            let paper = 230.0 - x as f32 * 0.8;
+            let ink = x % 20 < 3 && (20..40).contains(&y);
+            Luma([if ink { paper * 0.3 } else { paper } as u8])
+        }))
+    }
+
+    #[test]
+    fn test_otsu_threshold() {
+        let test_image_data = include_bytes!("../../sample.jpg").to_vec();
+        let binary_bytes = otsu_threshold(test_image_data).unwrap();
+
+        let binary_image = image::load_from_memory(&binary_bytes).unwrap();
+
+        binary_image.save("test-output/otsu_threshold.jpg").unwrap();
+    }
+
+    #[test]
+    fn test_otsu_level() {
+        let halves = DynamicImage::ImageLuma8(GrayImage::from_fn(10, 10, |x, _| {
+            Luma([if x < 5 { 40 } else { 200 }])
+        }));
+        let level = otsu_level(&halves);
+        assert!((40.0..200.0).contains(&level));
+
+        let binary = apply_otsu(&halves).to_luma8();
+        assert_eq!(binary.get_pixel(0, 0)[0], 0);
+        assert_eq!(binary.get_pixel(9, 0)[0], 255);
+    }
+
+    #[test]
+    fn test_adaptive_thresholds_handle_shadows() {
+        let page = shaded_page();
+        let ink = [(1, 30), (101, 30), (181, 30)];
+        let paper = [(10, 5), (110, 50), (190, 5)];
+
+        // One global level either loses the text on the bright side or
+        // blackens the paper in the shadow.
+        let global = apply_otsu(&page).to_luma8();
+        assert!(ink.iter().chain(&paper).any(|&(x, y)| {
+            let expected = if paper.contains(&(x, y)) { 255 } else { 0 };
+            global.get_pixel(x, y)[0] != expected
+        }));
+
+        for binary in [
+            apply_adaptive(&page, 25, 10.0),
+            apply_sauvola(&page, 25, 0.3),
+        ] {
+            let binary = binary.to_luma8();
+            for (x, y) in ink {
+                assert_eq!(binary.get_pixel(x, y)[0], 0, "ink at {x}, {y}");
+            }
+            for (x, y) in paper {
+                assert_eq!(binary.get_pixel(x, y)[0], 255, "paper at {x}, {y}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_threshold_writes_1bit_png() {
+        let png = write_image(&shaded_page(), ImageFormat::Png).unwrap();
+        let binary = threshold(png, 128.0).unwrap();
+
+        let decoder = png::Decoder::new(std::io::Cursor::new(binary));
+        let info = decoder.read_info().unwrap().info().clone();
+        assert_eq!(info.bit_depth, png::BitDepth::One);
+    }
+}
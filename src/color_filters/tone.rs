@@ -0,0 +1,221 @@
+use std::io::Cursor;
+
+use image::{
+    DynamicImage,
+    imageops::{self, FilterType},
+};
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use crate::utils::color_space::{linear_to_srgb, srgb_to_linear};
+use crate::utils::pixels::{map_channels, map_luminance};
+use crate::utils::read_image::read_image;
+
+/// Linear light value above which `exposure` rolls highlights off instead of clipping.
+const SHOULDER: f32 = 0.8;
+
+/// Long side, in pixels, of the brightness map that shadows and highlights are found on.
+const MASK_SIZE: u32 = 128;
+
+/// Blur of the brightness map, relative to `MASK_SIZE`. Large enough that
+/// whole areas are lifted or darkened together and local contrast survives.
+const MASK_SIGMA: f32 = 4.0;
+
+/// Change the exposure by `ev` stops, like a camera: +1 doubles the light and
+/// -1 halves it. Works in linear light, and bright areas roll off smoothly
+/// instead of clipping to white.
+#[wasm_bindgen]
+pub fn exposure(image_data: Vec<u8>, ev: f32) -> Result<Vec<u8>, JsValue> {
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    let mut buf = Vec::new();
+
+    apply_exposure(&image, ev)
+        .write_to(&mut Cursor::new(&mut buf), format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to change exposure: {err}")))?;
+
+    Ok(buf)
+}
+
+/// Lift (positive) or deepen (negative) the dark areas of the image, -100 to 100.
+/// Bright areas are left alone, so a backlit face can be brightened without
+/// washing out the sky.
+#[wasm_bindgen]
+pub fn shadows(image_data: Vec<u8>, amount: f32) -> Result<Vec<u8>, JsValue> {
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    let mut buf = Vec::new();
+
+    apply_shadows_highlights(&image, amount, 0.0)
+        .write_to(&mut Cursor::new(&mut buf), format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to adjust shadows: {err}")))?;
+
+    Ok(buf)
+}
+
+/// Recover (negative) or brighten (positive) the bright areas of the image,
+/// -100 to 100. Dark areas are left alone.
+#[wasm_bindgen]
+pub fn highlights(image_data: Vec<u8>, amount: f32) -> Result<Vec<u8>, JsValue> {
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    let mut buf = Vec::new();
+
+    apply_shadows_highlights(&image, 0.0, amount)
+        .write_to(&mut Cursor::new(&mut buf), format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to adjust highlights: {err}")))?;
+
+    Ok(buf)
+}
+
+/// Change the exposure of an already decoded image.
+pub(crate) fn apply_exposure(image: &DynamicImage, ev: f32) -> DynamicImage {
+    let gain = 2f32.powf(ev.clamp(-10.0, 10.0));
+    // Extended Reinhard above the shoulder: slope 1 where it starts and the
+    // brightest possible value, white times `gain`, lands exactly on white.
+    let white = (gain - SHOULDER) / (1.0 - SHOULDER);
+
+    let mut image = image.clone();
+    map_channels(&mut image, |_, value| {
+        let light = srgb_to_linear(value) * gain;
+        if gain <= 1.0 || light <= SHOULDER {
+            return linear_to_srgb(light);
+        }
+        let x = (light - SHOULDER) / (1.0 - SHOULDER);
+        let rolled = x * (1.0 + x / (white * white)) / (1.0 + x);
+        linear_to_srgb(SHOULDER + (1.0 - SHOULDER) * rolled)
+    });
+    image
+}
+
+/// Adjust the shadows and highlights of an already decoded image.
+/// Each pixel is weighed by the brightness of its surroundings, not its own,
+/// so texture inside a dark or bright area keeps its contrast.
+pub(crate) fn apply_shadows_highlights(
+    image: &DynamicImage,
+    shadows: f32,
+    highlights: f32,
+) -> DynamicImage {
+    let shadows = shadows.clamp(-100.0, 100.0) / 100.0;
+    let highlights = highlights.clamp(-100.0, 100.0) / 100.0;
+    if shadows == 0.0 && highlights == 0.0 {
+        return image.clone();
+    }
+
+    let surroundings = surroundings(image);
+    let mut image = image.clone();
+    map_luminance(&mut image, |index, luminance| {
+        let around = surroundings[index];
+        let mut luminance = luminance;
+
+        let weight = 1.0 - smoothstep(0.0, 0.6, around);
+        if weight > 0.0 && shadows != 0.0 {
+            let gamma = 1.0 + shadows.abs() * weight * 1.5;
+            let gamma = if shadows > 0.0 { 1.0 / gamma } else { gamma };
+            luminance = luminance.clamp(0.0, 1.0).powf(gamma);
+        }
+
+        let weight = smoothstep(0.4, 1.0, around);
+        if weight > 0.0 && highlights != 0.0 {
+            let gamma = 1.0 + highlights.abs() * weight * 1.5;
+            let gamma = if highlights > 0.0 { gamma } else { 1.0 / gamma };
+            luminance = 1.0 - (1.0 - luminance.clamp(0.0, 1.0)).powf(gamma);
+        }
+
+        luminance
+    });
+    image
+}
+
+/// A heavily blurred brightness map of the image, one value per pixel.
+fn surroundings(image: &DynamicImage) -> Vec<f32> {
+    let luma = image.to_luma32f();
+    let (width, height) = luma.dimensions();
+    let scale = (MASK_SIZE as f32 / width.max(height) as f32).min(1.0);
+    let small_width = ((width as f32 * scale).round() as u32).max(1);
+    let small_height = ((height as f32 * scale).round() as u32).max(1);
+
+    let small = imageops::resize(&luma, small_width, small_height, FilterType::Triangle);
+    let blurred = imageops::blur(
+        &small,
+        MASK_SIGMA * small_width.max(small_height) as f32 / MASK_SIZE as f32,
+    );
+    imageops::resize(&blurred, width, height, FilterType::Triangle).into_raw()
+}
+
+fn smoothstep(edge0: f32, edge1: f32, value: f32) -> f32 {
+    let t = ((value - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+#[cfg(test)]
+mod test {
+    use image::{GrayImage, Luma, Rgb, RgbImage};
+
+    use super::*;
+
+    #[test]
+    fn test_exposure() {
+        let test_image_data = include_bytes!("../../sample.jpg").to_vec();
+        let exposed_bytes = exposure(test_image_data, 1.0).unwrap();
+
+        let exposed_image = image::load_from_memory(&exposed_bytes).unwrap();
+
+        exposed_image.save("test-output/exposure.jpg").unwrap();
+    }
+
+    #[test]
+    fn test_shadows() {
+        let test_image_data = include_bytes!("../../sample.jpg").to_vec();
+        let lifted_bytes = shadows(test_image_data, 60.0).unwrap();
+
+        let lifted_image = image::load_from_memory(&lifted_bytes).unwrap();
+
+        lifted_image.save("test-output/shadows.jpg").unwrap();
+    }
+
+    #[test]
+    fn test_exposure_stops() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_fn(256, 1, |x, _| Rgb([x as u8; 3])));
+
+        assert_eq!(apply_exposure(&image, 0.0).to_rgb8(), image.to_rgb8());
+
+        // Middle gray is 0.214 in linear light, one stop down is 0.107.
+        let darker = apply_exposure(&image, -1.0).to_rgb8();
+        assert!(darker.get_pixel(128, 0)[0].abs_diff(93) <= 1);
+
+        let brighter = apply_exposure(&image, 2.0).to_rgb8();
+        let row: Vec<u8> = (0..256).map(|x| brighter.get_pixel(x, 0)[0]).collect();
+        assert!(row.windows(2).all(|pair| pair[1] >= pair[0]));
+        assert!(row[200] < row[255], "highlights were clipped");
+    }
+
+    #[test]
+    fn test_shadows_and_highlights_are_local() {
+        // A dark half and a bright half, each with some texture.
+        let image = DynamicImage::ImageLuma8(GrayImage::from_fn(200, 100, |x, y| {
+            let base = if x < 100 { 30 } else { 220 };
+            Luma([base + (y % 2) as u8 * 10])
+        }));
+
+        let lifted = apply_shadows_highlights(&image, 80.0, 0.0).to_luma8();
+        assert!(lifted.get_pixel(20, 50)[0] > 60);
+        assert!(lifted.get_pixel(20, 51)[0] > lifted.get_pixel(20, 50)[0]);
+        assert_eq!(lifted.get_pixel(180, 50)[0], 220);
+
+        let recovered = apply_shadows_highlights(&image, 0.0, -80.0).to_luma8();
+        assert!(recovered.get_pixel(180, 50)[0] < 200);
+        assert_eq!(recovered.get_pixel(20, 50)[0], 30);
+    }
+}
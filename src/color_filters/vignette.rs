@@ -0,0 +1,85 @@
+use std::io::Cursor;
+
+use image::{DynamicImage, ImageBuffer, Pixel};
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use crate::utils::pixels::{color_channels, from_unit, to_unit, with_buffer};
+use crate::utils::read_image::read_image;
+
+/// Distance from the center, as a fraction of the way to the corners,
+/// where the vignette starts to darken.
+const VIGNETTE_START: f32 = 0.4;
+
+/// Darken the image towards its corners.
+/// `strength` of 0 does nothing and 1.0 turns the corners black.
+#[wasm_bindgen]
+pub fn vignette(image_data: Vec<u8>, strength: f32) -> Result<Vec<u8>, JsValue> {
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    let mut buf = Vec::new();
+
+    apply(&image, strength)
+        .write_to(&mut Cursor::new(&mut buf), format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to add vignette: {err}")))?;
+
+    Ok(buf)
+}
+
+/// Add a vignette to an already decoded image.
+pub(crate) fn apply(image: &DynamicImage, strength: f32) -> DynamicImage {
+    let mut image = image.clone();
+    let strength = strength.clamp(0.0, 1.0);
+    with_buffer!(&mut image, buffer => vignette_buffer(buffer, strength));
+    image
+}
+
+fn vignette_buffer<P: Pixel>(buffer: &mut ImageBuffer<P, Vec<P::Subpixel>>, strength: f32) {
+    let (width, height) = buffer.dimensions();
+    let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+    let color_channels = color_channels::<P>();
+
+    for (x, y, pixel) in buffer.enumerate_pixels_mut() {
+        // 0 in the center, 1 in the corners, following the image's aspect ratio.
+        let dx = (x as f32 + 0.5 - cx) / cx;
+        let dy = (y as f32 + 0.5 - cy) / cy;
+        let distance = dx.hypot(dy) / std::f32::consts::SQRT_2;
+
+        let t = ((distance - VIGNETTE_START) / (1.0 - VIGNETTE_START)).clamp(0.0, 1.0);
+        let factor = 1.0 - strength * t * t * (3.0 - 2.0 * t);
+
+        for value in &mut pixel.channels_mut()[..color_channels] {
+            *value = from_unit(to_unit(*value) * factor);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use image::{Rgb, RgbImage};
+
+    use super::*;
+
+    #[test]
+    fn test_vignette() {
+        let test_image_data = include_bytes!("../../sample.jpg").to_vec();
+        let vignette_bytes = vignette(test_image_data, 0.6).unwrap();
+
+        let vignette_image = image::load_from_memory(&vignette_bytes).unwrap();
+
+        vignette_image.save("test-output/vignette.jpg").unwrap();
+    }
+
+    #[test]
+    fn test_vignette_darkens_corners_only() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(20, 10, Rgb([200; 3])));
+        let darkened = apply(&image, 1.0).to_rgb8();
+
+        assert_eq!(darkened.get_pixel(10, 5)[0], 200);
+        assert!(darkened.get_pixel(0, 0)[0] < 10);
+        assert!(darkened.get_pixel(19, 9)[0] < 10);
+    }
+}
@@ -0,0 +1,299 @@
+use std::io::Cursor;
+
+use image::DynamicImage;
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use super::color_matrix::ColorMatrix;
+use crate::utils::pixels::LUMA;
+use crate::utils::read_image::read_image;
+
+/// Color temperature, in Kelvin, that `temperature` treats as neutral.
+const NEUTRAL_KELVIN: f32 = 6500.0;
+
+/// Share of the brightest pixels `WhiteBalance::WhitePatch` ignores, in percent.
+const WHITE_PATCH_CLIP: f32 = 1.0;
+
+/// How `auto_white_balance` finds the color of the light.
+#[wasm_bindgen]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum WhiteBalance {
+    /// Assume the whole image averages out to gray. Works for most scenes.
+    #[default]
+    GrayWorld,
+    /// Assume the brightest part of the image is white, for scenes dominated by one color.
+    WhitePatch,
+}
+
+/// Warm (positive) or cool (negative) the image, as if the light had been
+/// `kelvin_shift` Kelvin lower or higher. 1000 is a clear change, brightness is kept.
+#[wasm_bindgen]
+pub fn temperature(image_data: Vec<u8>, kelvin_shift: f32) -> Result<Vec<u8>, JsValue> {
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    let mut buf = Vec::new();
+
+    temperature_matrix(kelvin_shift)
+        .apply(&image)
+        .write_to(&mut Cursor::new(&mut buf), format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to change the temperature: {err}")))?;
+
+    Ok(buf)
+}
+
+/// Shift the colors towards magenta (positive) or green (negative), -100 to 100.
+/// Use it with `temperature` to correct fluorescent or mixed light.
+#[wasm_bindgen]
+pub fn tint(image_data: Vec<u8>, value: f32) -> Result<Vec<u8>, JsValue> {
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    let mut buf = Vec::new();
+
+    tint_matrix(value)
+        .apply(&image)
+        .write_to(&mut Cursor::new(&mut buf), format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to tint: {err}")))?;
+
+    Ok(buf)
+}
+
+/// Remove the color cast of the light automatically, see `WhiteBalance`.
+#[wasm_bindgen]
+pub fn auto_white_balance(image_data: Vec<u8>, method: WhiteBalance) -> Result<Vec<u8>, JsValue> {
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    let mut buf = Vec::new();
+
+    auto_matrix(&image, method)
+        .apply(&image)
+        .write_to(&mut Cursor::new(&mut buf), format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to white balance: {err}")))?;
+
+    Ok(buf)
+}
+
+/// White balance so the pixel at `x`, `y` becomes gray.
+/// Pick something that should be neutral, like a white wall or gray card;
+/// the pixels right around it are averaged to ignore noise.
+#[wasm_bindgen]
+pub fn white_balance_at(image_data: Vec<u8>, x: u32, y: u32) -> Result<Vec<u8>, JsValue> {
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    let matrix = neutral_point_matrix(&image, x, y).map_err(|err| JsValue::from_str(&err))?;
+
+    let mut buf = Vec::new();
+
+    matrix
+        .apply(&image)
+        .write_to(&mut Cursor::new(&mut buf), format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to white balance: {err}")))?;
+
+    Ok(buf)
+}
+
+/// Channel gains that warm or cool the image by `kelvin_shift`.
+pub(crate) fn temperature_matrix(kelvin_shift: f32) -> ColorMatrix {
+    let target = blackbody(NEUTRAL_KELVIN - kelvin_shift);
+    let neutral = blackbody(NEUTRAL_KELVIN);
+    gains([0, 1, 2].map(|c| target[c] / neutral[c]))
+}
+
+/// Channel gains that move the colors between green and magenta.
+pub(crate) fn tint_matrix(value: f32) -> ColorMatrix {
+    let green = 1.0 - 0.3 * value.clamp(-100.0, 100.0) / 100.0;
+    gains([1.0, green, 1.0])
+}
+
+/// Channel gains that neutralise the light found with `method`.
+pub(crate) fn auto_matrix(image: &DynamicImage, method: WhiteBalance) -> ColorMatrix {
+    let rgb = image.to_rgb32f();
+    let light = match method {
+        WhiteBalance::GrayWorld => {
+            let mut sums = [0f64; 3];
+            for pixel in rgb.pixels() {
+                for (sum, &value) in sums.iter_mut().zip(&pixel.0) {
+                    *sum += value as f64;
+                }
+            }
+            sums.map(|sum| (sum / rgb.len().max(1) as f64 * 3.0) as f32)
+        }
+        WhiteBalance::WhitePatch => [0, 1, 2].map(|c| {
+            let mut values: Vec<f32> = rgb.pixels().map(|pixel| pixel[c]).collect();
+            if values.is_empty() {
+                return 1.0;
+            }
+            let index = ((values.len() as f32 * (1.0 - WHITE_PATCH_CLIP / 100.0)) as usize)
+                .min(values.len() - 1);
+            *values.select_nth_unstable_by(index, f32::total_cmp).1
+        }),
+    };
+    neutralise(light)
+}
+
+/// Channel gains that turn the color around `x`, `y` gray.
+pub(crate) fn neutral_point_matrix(
+    image: &DynamicImage,
+    x: u32,
+    y: u32,
+) -> Result<ColorMatrix, String> {
+    let (width, height) = (image.width(), image.height());
+    if x >= width || y >= height {
+        return Err(format!(
+            "The point {x}, {y} is outside the {width}x{height} image"
+        ));
+    }
+
+    let area = image
+        .crop_imm(x.saturating_sub(1), y.saturating_sub(1), 3, 3)
+        .to_rgb32f();
+    let mut sums = [0f32; 3];
+    for pixel in area.pixels() {
+        for (sum, &value) in sums.iter_mut().zip(&pixel.0) {
+            *sum += value;
+        }
+    }
+    Ok(neutralise(sums))
+}
+
+/// Gains that make a light of color `light` gray, keeping its brightness.
+fn neutralise(light: [f32; 3]) -> ColorMatrix {
+    if light.iter().any(|&value| value <= 0.0) {
+        return ColorMatrix::IDENTITY;
+    }
+    gains(light.map(|value| 1.0 / value))
+}
+
+/// Scale each color channel, normalised so the luminance stays the same.
+fn gains(gains: [f32; 3]) -> ColorMatrix {
+    let luminance: f32 = gains
+        .iter()
+        .zip(LUMA)
+        .map(|(gain, weight)| gain * weight)
+        .sum();
+    let [r, g, b] = gains.map(|gain| gain / luminance);
+    ColorMatrix::rgb([[r, 0.0, 0.0], [0.0, g, 0.0], [0.0, 0.0, b]])
+}
+
+/// Approximate color of a black body at `kelvin`, each channel in `0.0..=1.0`.
+/// Fit by Tanner Helland, good from 1000 to 40000 Kelvin.
+fn blackbody(kelvin: f32) -> [f32; 3] {
+    let t = kelvin.clamp(1000.0, 40000.0) / 100.0;
+
+    let red = if t <= 66.0 {
+        255.0
+    } else {
+        329.698_73 * (t - 60.0).powf(-0.133_204_76)
+    };
+    let green = if t <= 66.0 {
+        99.470_8 * t.ln() - 161.119_57
+    } else {
+        288.122_17 * (t - 60.0).powf(-0.075_514_85)
+    };
+    let blue = if t >= 66.0 {
+        255.0
+    } else if t <= 19.0 {
+        0.0
+    } else {
+        138.517_73 * (t - 10.0).ln() - 305.044_8
+    };
+
+    // Keep a little blue even for candle light so the gains stay finite.
+    [red, green, blue].map(|value| value.clamp(1.0, 255.0) / 255.0)
+}
+
+#[cfg(test)]
+mod test {
+    use image::{Rgb, RgbImage};
+
+    use super::*;
+
+    #[test]
+    fn test_temperature() {
+        let test_image_data = include_bytes!("../../sample.jpg").to_vec();
+        let warm_bytes = temperature(test_image_data, 1500.0).unwrap();
+
+        let warm_image = image::load_from_memory(&warm_bytes).unwrap();
+
+        warm_image.save("test-output/temperature.jpg").unwrap();
+    }
+
+    #[test]
+    fn test_auto_white_balance() {
+        let test_image_data = include_bytes!("../../sample.jpg").to_vec();
+        let balanced_bytes = auto_white_balance(test_image_data, WhiteBalance::GrayWorld).unwrap();
+
+        let balanced_image = image::load_from_memory(&balanced_bytes).unwrap();
+
+        balanced_image
+            .save("test-output/auto_white_balance.jpg")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_temperature_and_tint() {
+        let gray = DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 2, Rgb([128; 3])));
+
+        let warm = *temperature_matrix(2000.0)
+            .apply(&gray)
+            .to_rgb8()
+            .get_pixel(0, 0);
+        assert!(warm[0] > warm[2] + 20, "not warmer: {warm:?}");
+        let cool = *temperature_matrix(-2000.0)
+            .apply(&gray)
+            .to_rgb8()
+            .get_pixel(0, 0);
+        assert!(cool[2] > cool[0] + 20, "not cooler: {cool:?}");
+
+        let magenta = *tint_matrix(50.0).apply(&gray).to_rgb8().get_pixel(0, 0);
+        assert!(magenta[1] < magenta[0] && magenta[0] == magenta[2]);
+        assert_eq!(
+            *temperature_matrix(0.0)
+                .apply(&gray)
+                .to_rgb8()
+                .get_pixel(0, 0),
+            Rgb([128; 3])
+        );
+    }
+
+    #[test]
+    fn test_auto_white_balance_removes_cast() {
+        // A gray ramp photographed under blueish light.
+        let image = DynamicImage::ImageRgb8(RgbImage::from_fn(64, 4, |x, _| {
+            let value = 40 + x * 2;
+            Rgb([value * 8 / 10, value * 9 / 10, value].map(|channel| channel as u8))
+        }));
+        let is_gray =
+            |pixel: Rgb<u8>| pixel[0].abs_diff(pixel[1]) <= 3 && pixel[1].abs_diff(pixel[2]) <= 3;
+
+        for method in [WhiteBalance::GrayWorld, WhiteBalance::WhitePatch] {
+            let balanced = auto_matrix(&image, method).apply(&image).to_rgb8();
+            assert!(
+                is_gray(*balanced.get_pixel(40, 0)),
+                "{method:?}: {:?}",
+                balanced.get_pixel(40, 0)
+            );
+        }
+
+        let picked = neutral_point_matrix(&image, 10, 2)
+            .unwrap()
+            .apply(&image)
+            .to_rgb8();
+        assert!(is_gray(*picked.get_pixel(50, 0)));
+        assert!(neutral_point_matrix(&image, 64, 0).is_err());
+    }
+}
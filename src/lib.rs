@@ -4,6 +4,10 @@ pub mod color_filters;
 pub mod transformation;
 mod utils;
 pub mod chaining;
+pub mod animation;
+pub mod memory;
+pub mod region;
+pub mod presets;
 
 #[wasm_bindgen]
 extern "C" {
@@ -0,0 +1,69 @@
+use wasm_bindgen::{JsValue, prelude::wasm_bindgen};
+
+/// A byte buffer that lives in wasm memory.
+///
+/// Large images can be written straight into it from JS and read back
+/// through a view, instead of being copied in and out on every call.
+/// The buffer is owned by wasm, call `free()` once it is no longer needed.
+///
+/// ```javascript
+/// const input = WasmBuffer.new(file.size);
+/// new Uint8Array(wasm_memory().buffer, input.ptr(), input.len()).set(fileBytes);
+///
+/// const output = ImageProcessor.from_buffer(input).resize(1024, 1024).process_to_buffer();
+/// input.free();
+///
+/// // The view is only valid until wasm memory grows, copy it or use it right away.
+/// const view = new Uint8Array(wasm_memory().buffer, output.ptr(), output.len());
+/// const blob = new Blob([view], { type: file.type });
+/// output.free();
+/// ```
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct WasmBuffer {
+    data: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl WasmBuffer {
+    /// Allocate a zeroed buffer of `len` bytes.
+    pub fn new(len: usize) -> Self {
+        Self { data: vec![0; len] }
+    }
+
+    /// Offset of the first byte in wasm memory.
+    pub fn ptr(&self) -> *const u8 {
+        self.data.as_ptr()
+    }
+
+    /// Number of bytes in the buffer.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+impl WasmBuffer {
+    pub(crate) fn as_slice(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub(crate) fn into_vec(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+impl From<Vec<u8>> for WasmBuffer {
+    fn from(data: Vec<u8>) -> Self {
+        Self { data }
+    }
+}
+
+/// The wasm memory, so JS can create views over a `WasmBuffer`.
+#[wasm_bindgen]
+pub fn wasm_memory() -> JsValue {
+    wasm_bindgen::memory()
+}
@@ -0,0 +1,181 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use image::DynamicImage;
+use serde::Deserialize;
+use wasm_bindgen::{JsValue, prelude::wasm_bindgen};
+
+use crate::color_filters::{
+    brighten, color_matrix::ColorMatrix, contrast, grain, grayscale, hsl, hue_rotate, saturate,
+    sepia, vignette,
+};
+use crate::utils::{pixels::blend, read_image::read_image, write_image::write_image};
+
+/// The looks that ship with the library, in the same format `register_presets` takes.
+const BUILT_IN: &str = include_str!("presets.json");
+
+thread_local! {
+    static PRESETS: RefCell<BTreeMap<String, Vec<Step>>> = RefCell::new(
+        parse(BUILT_IN).expect("the built-in presets are valid"),
+    );
+}
+
+/// One operation of a preset, tagged by `op` in JSON.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case", deny_unknown_fields)]
+pub(crate) enum Step {
+    Brighten { value: i32 },
+    Contrast { value: f32 },
+    HueRotate { degrees: i32 },
+    Grayscale,
+    Saturate { amount: f32 },
+    Saturation { amount: f32 },
+    Vibrance { amount: f32 },
+    Sepia { amount: f32 },
+    ColorMatrix { matrix: ColorMatrix },
+    Vignette { strength: f32 },
+    Grain { amount: f32 },
+}
+
+/// Apply a named one-tap look, like `"vintage"`, `"noir"`, `"warm"`, `"cool"`,
+/// `"fade"`, `"vivid"`, `"lush"` or `"dusk"`, or one added with `register_presets`.
+/// `intensity` blends between the original (0.0) and the full look (1.0).
+#[wasm_bindgen]
+pub fn apply_preset(image_data: Vec<u8>, name: &str, intensity: f32) -> Result<Vec<u8>, JsValue> {
+    let steps = steps(name).map_err(|err| JsValue::from_str(&err))?;
+
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get image type: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
+
+    write_image(&apply(&image, &steps, intensity), format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to encode the image: {err}")))
+}
+
+/// Add or replace presets from JSON: an object of preset names, each a list
+/// of steps applied in order.
+///
+/// ```javascript
+/// register_presets(JSON.stringify({
+///   golden: [
+///     { op: "sepia", amount: 0.2 },
+///     { op: "saturate", amount: 1.3 },
+///     { op: "vignette", strength: 0.25 },
+///   ],
+/// }));
+/// ```
+///
+/// Steps are `brighten` (`value`), `contrast` (`value`), `hue_rotate` (`degrees`),
+/// `grayscale`, `saturate` (`amount`), `saturation` and `vibrance` (`amount`,
+/// -100 to 100), `sepia` (`amount`), `color_matrix` (`matrix`, 20 values),
+/// `vignette` (`strength`) and `grain` (`amount`).
+#[wasm_bindgen]
+pub fn register_presets(json: &str) -> Result<(), JsValue> {
+    let presets = parse(json).map_err(|err| JsValue::from_str(&err))?;
+    PRESETS.with(|registry| registry.borrow_mut().extend(presets));
+    Ok(())
+}
+
+/// Names of every available preset, built-in and registered, sorted.
+#[wasm_bindgen]
+pub fn preset_names() -> Vec<String> {
+    PRESETS.with(|registry| registry.borrow().keys().cloned().collect())
+}
+
+/// The steps of a preset, ready to apply with `apply`.
+pub(crate) fn steps(name: &str) -> Result<Vec<Step>, String> {
+    PRESETS
+        .with(|registry| registry.borrow().get(name).cloned())
+        .ok_or_else(|| format!("Unknown preset: {name}"))
+}
+
+/// Apply preset steps to an already decoded image.
+pub(crate) fn apply(image: &DynamicImage, steps: &[Step], intensity: f32) -> DynamicImage {
+    let intensity = intensity.clamp(0.0, 1.0);
+    if intensity == 0.0 {
+        return image.clone();
+    }
+
+    let graded = steps
+        .iter()
+        .fold(image.clone(), |graded, step| step.apply(&graded));
+
+    if intensity == 1.0 {
+        graded
+    } else {
+        blend(image, graded, |_| intensity)
+    }
+}
+
+impl Step {
+    fn apply(&self, image: &DynamicImage) -> DynamicImage {
+        match self {
+            Step::Brighten { value } => brighten::apply(image, *value),
+            Step::Contrast { value } => contrast::apply(image, *value),
+            Step::HueRotate { degrees } => hue_rotate::apply(image, *degrees),
+            Step::Grayscale => grayscale::apply(image),
+            Step::Saturate { amount } => saturate::apply(image, *amount),
+            Step::Saturation { amount } => hsl::apply_saturation(image, *amount),
+            Step::Vibrance { amount } => hsl::apply_vibrance(image, *amount),
+            Step::Sepia { amount } => sepia::apply(image, *amount),
+            Step::ColorMatrix { matrix } => matrix.apply(image),
+            Step::Vignette { strength } => vignette::apply(image, *strength),
+            Step::Grain { amount } => grain::apply(image, *amount),
+        }
+    }
+}
+
+fn parse(json: &str) -> Result<BTreeMap<String, Vec<Step>>, String> {
+    serde_json::from_str(json).map_err(|err| format!("Failed to read presets: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgb, RgbImage};
+
+    use super::*;
+
+    #[test]
+    fn test_built_in_presets() {
+        let image = read_image(include_bytes!("../../sample.jpg").as_slice()).unwrap();
+        let image = image.thumbnail(400, 400);
+
+        for name in preset_names() {
+            let looked = apply(&image, &steps(&name).unwrap(), 1.0);
+            looked
+                .to_rgb8()
+                .save(format!("test-output/preset_{name}.jpg"))
+                .unwrap();
+        }
+        assert!(preset_names().len() >= 5);
+    }
+
+    #[test]
+    fn test_intensity() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(4, 4, Rgb([200, 100, 50])));
+        let steps = steps("noir").unwrap();
+
+        assert_eq!(apply(&image, &steps, 0.0).to_rgb8(), image.to_rgb8());
+        let full = apply(&image, &steps, 1.0).to_rgb8();
+        let half = apply(&image, &steps, 0.5).to_rgb8();
+        let (full, half) = (full.get_pixel(1, 1)[0], half.get_pixel(1, 1)[0]);
+        assert!(half.abs_diff(((200 + full as u16) / 2) as u8) <= 1);
+    }
+
+    #[test]
+    fn test_register_presets() {
+        register_presets(
+            r#"{ "soft_noir": [{ "op": "grayscale" }, { "op": "contrast", "value": 10 }] }"#,
+        )
+        .unwrap();
+
+        assert!(preset_names().contains(&"soft_noir".to_string()));
+        assert_eq!(steps("soft_noir").unwrap().len(), 2);
+        assert!(steps("missing").is_err());
+        assert!(parse(r#"{ "bad": [{ "op": "glow" }] }"#).is_err());
+        let short_matrix = parse(r#"{ "bad": [{ "op": "color_matrix", "matrix": [1, 0] }] }"#);
+        assert!(short_matrix.unwrap_err().contains("20 values"));
+    }
+}
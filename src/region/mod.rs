@@ -0,0 +1,226 @@
+use image::{
+    DynamicImage, GrayImage,
+    imageops::{self, FilterType},
+};
+use wasm_bindgen::{JsError, prelude::wasm_bindgen};
+
+use crate::utils::{
+    pixels::{blend, from_unit, to_unit},
+    read_image::read_image,
+};
+
+/// Part of an image that filters on `ImageProcessor` are restricted to.
+///
+/// ```javascript
+/// const output = new ImageProcessor(bytes)
+///   .region(Region.ellipse(320, 240, 120, 160).feather(24))
+///   .blur(8.0)
+///   .clear_region()
+///   .contrast(10.0)
+///   .process();
+/// ```
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct Region {
+    shape: Shape,
+    feather: f32,
+}
+
+#[derive(Debug, Clone)]
+enum Shape {
+    Rect {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+    },
+    Ellipse {
+        cx: f32,
+        cy: f32,
+        rx: f32,
+        ry: f32,
+    },
+    Mask(GrayImage),
+}
+
+#[wasm_bindgen]
+impl Region {
+    /// A rectangle with its top left corner at `x`, `y`.
+    pub fn rect(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self::from_shape(Shape::Rect {
+            x: x as f32,
+            y: y as f32,
+            width: width as f32,
+            height: height as f32,
+        })
+    }
+
+    /// An ellipse centered on `cx`, `cy` with the radii `rx` and `ry`.
+    pub fn ellipse(cx: f32, cy: f32, rx: f32, ry: f32) -> Self {
+        Self::from_shape(Shape::Ellipse {
+            cx,
+            cy,
+            rx: rx.max(f32::EPSILON),
+            ry: ry.max(f32::EPSILON),
+        })
+    }
+
+    /// A mask from image bytes. White (or opaque, for images with an alpha
+    /// channel) is filtered, black (or transparent) is kept as is.
+    /// Masks of a different size are stretched over the image.
+    pub fn mask(image_data: Vec<u8>) -> Result<Region, JsError> {
+        let mask = read_image(image_data)
+            .map_err(|err| JsError::new(&format!("Failed to read mask: {err}")))?;
+
+        let mask = if mask.color().has_alpha() {
+            let alpha = mask.to_luma_alpha8();
+            GrayImage::from_fn(alpha.width(), alpha.height(), |x, y| {
+                image::Luma([alpha.get_pixel(x, y)[1]])
+            })
+        } else {
+            mask.to_luma8()
+        };
+
+        Ok(Self::from_shape(Shape::Mask(mask)))
+    }
+
+    /// Soften the edge of the region over `radius` pixels.
+    /// Rectangles and ellipses fade out beyond their edge, so the area
+    /// inside is always fully filtered. Masks are blurred by the radius.
+    pub fn feather(mut self, radius: f32) -> Self {
+        self.feather = radius.max(0.0);
+        self
+    }
+}
+
+impl Region {
+    fn from_shape(shape: Shape) -> Self {
+        Self {
+            shape,
+            feather: 0.0,
+        }
+    }
+
+    /// Mix `filtered` into `original`, following the region.
+    /// Filters that changed the image size are not restricted.
+    pub(crate) fn blend(&self, original: &DynamicImage, filtered: DynamicImage) -> DynamicImage {
+        if original.width() != filtered.width() || original.height() != filtered.height() {
+            return filtered;
+        }
+
+        let weights = self.weights(original.width(), original.height());
+        blend(original, filtered, |index| to_unit(weights.as_raw()[index]))
+    }
+
+    /// How much of the filtered image shows at every pixel, from 0 to 1.
+    fn weights(&self, width: u32, height: u32) -> GrayImage {
+        match &self.shape {
+            Shape::Mask(mask) => {
+                let mut mask = if mask.dimensions() == (width, height) {
+                    mask.clone()
+                } else {
+                    imageops::resize(mask, width, height, FilterType::Triangle)
+                };
+                if self.feather > 0.0 {
+                    mask = imageops::blur(&mask, self.feather / 2.0);
+                }
+                mask
+            }
+            shape => GrayImage::from_fn(width, height, |x, y| {
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                let distance = shape.distance_outside(px, py);
+                image::Luma([from_unit(self.falloff(distance))])
+            }),
+        }
+    }
+
+    fn falloff(&self, distance: f32) -> f32 {
+        if distance <= 0.0 {
+            1.0
+        } else if distance >= self.feather {
+            0.0
+        } else {
+            let t = 1.0 - distance / self.feather;
+            t * t * (3.0 - 2.0 * t)
+        }
+    }
+}
+
+impl Shape {
+    /// Distance from the point to the shape, 0 or less inside it.
+    fn distance_outside(&self, px: f32, py: f32) -> f32 {
+        match *self {
+            Shape::Rect {
+                x,
+                y,
+                width,
+                height,
+            } => {
+                let dx = (x - px).max(px - (x + width)).max(0.0);
+                let dy = (y - py).max(py - (y + height)).max(0.0);
+                if dx == 0.0 && dy == 0.0 {
+                    0.0
+                } else {
+                    dx.hypot(dy)
+                }
+            }
+            Shape::Ellipse { cx, cy, rx, ry } => {
+                let (dx, dy) = (px - cx, py - cy);
+                let r = ((dx / rx).powi(2) + (dy / ry).powi(2)).sqrt();
+                if r <= 1.0 {
+                    0.0
+                } else {
+                    // Distance to the edge along the ray from the center.
+                    dx.hypot(dy) * (1.0 - 1.0 / r)
+                }
+            }
+            Shape::Mask(_) => 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use image::{Rgb, RgbImage};
+
+    use super::*;
+
+    fn gray_square(value: u8) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(20, 20, Rgb([value; 3])))
+    }
+
+    #[test]
+    fn test_rect_region() {
+        let blended = Region::rect(5, 5, 10, 10).blend(&gray_square(0), gray_square(200));
+        let blended = blended.to_rgb8();
+
+        assert_eq!(blended.get_pixel(5, 5)[0], 200);
+        assert_eq!(blended.get_pixel(14, 14)[0], 200);
+        assert_eq!(blended.get_pixel(4, 10)[0], 0);
+        assert_eq!(blended.get_pixel(15, 10)[0], 0);
+    }
+
+    #[test]
+    fn test_feathered_ellipse() {
+        let region = Region::ellipse(10.0, 10.0, 4.0, 4.0).feather(4.0);
+        let blended = region.blend(&gray_square(0), gray_square(200)).to_rgb8();
+
+        assert_eq!(blended.get_pixel(10, 10)[0], 200);
+        let edge = blended.get_pixel(16, 10)[0];
+        assert!(edge > 0 && edge < 200, "edge value {edge} is not feathered");
+        assert_eq!(blended.get_pixel(19, 10)[0], 0);
+    }
+
+    #[test]
+    fn test_grayscale_filter_keeps_color_outside() {
+        let original = DynamicImage::ImageRgb8(RgbImage::from_pixel(8, 8, Rgb([255, 0, 0])));
+        let filtered = original.grayscale();
+        let blended = Region::rect(0, 0, 4, 8)
+            .blend(&original, filtered)
+            .to_rgb8();
+
+        let gray = blended.get_pixel(1, 1);
+        assert_eq!(gray[0], gray[1]);
+        assert_eq!(*blended.get_pixel(6, 1), Rgb([255, 0, 0]));
+    }
+}
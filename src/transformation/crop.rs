@@ -1,5 +1,6 @@
 use std::io::Cursor;
 
+use image::DynamicImage;
 use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
 
 use crate::utils::read_image::read_image;
@@ -16,18 +17,28 @@ pub fn crop(
     let format = image::guess_format(&image_data)
         .map_err(|err| JsValue::from_str(&format!("Failed to get the image format: {err}")))?;
 
-    let mut image = read_image(image_data)
+    let image = read_image(image_data)
         .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
 
     let mut buf = Vec::new();
-    image
-        .crop(x, y, width, height)
+    apply(&image, x, y, width, height)
         .write_to(&mut Cursor::new(&mut buf), format)
         .map_err(|err| JsValue::from_str(&format!("Failed to crop the image: {err}")))?;
 
     Ok(buf)
 }
 
+/// Cut a rectangle out of an already decoded image.
+pub(crate) fn apply(
+    image: &DynamicImage,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+) -> DynamicImage {
+    image.crop_imm(x, y, width, height)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
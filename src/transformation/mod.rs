@@ -2,3 +2,4 @@ pub mod resize;
 pub mod resize_square;
 pub mod thumbnail;
 pub mod crop;
+pub mod quality;
@@ -1,8 +1,9 @@
 use std::io::Cursor;
 
-use image::imageops::FilterType;
+use image::{imageops::FilterType, DynamicImage};
 use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
 
+use super::quality::ResizeQuality;
 use crate::utils::read_image::read_image;
 
 /// Resize an image
@@ -19,17 +20,56 @@ pub fn resize(image_data: Vec<u8>, width: u32, height: u32) -> Result<Vec<u8>, J
         .map_err(|err| JsValue::from_str(&format!("Failed to read image.: {err}")))?;
 
     let mut buf = Vec::new();
-    image
-        .resize(width, height, FilterType::Nearest)
+    apply(&image, width, height)
         .write_to(&mut Cursor::new(&mut buf), format)
         .map_err(|err| JsValue::from_str(&format!("Failed to resize the image: {err}")))?;
 
     Ok(buf)
 }
 
+/// Resize an image like `resize`, choosing the resampling quality.
+/// `ResizeQuality.Balanced` and `Best` work in linear light, which keeps fine
+/// detail and high contrast edges from getting darker when downscaling.
+#[wasm_bindgen]
+pub fn resize_with_quality(
+    image_data: Vec<u8>,
+    width: u32,
+    height: u32,
+    quality: ResizeQuality,
+) -> Result<Vec<u8>, JsValue> {
+    let format = image::guess_format(&image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to get the image format: {err}")))?;
+
+    let image = read_image(image_data)
+        .map_err(|err| JsValue::from_str(&format!("Failed to read image.: {err}")))?;
+
+    let mut buf = Vec::new();
+    apply_with(&image, width, height, quality)
+        .write_to(&mut Cursor::new(&mut buf), format)
+        .map_err(|err| JsValue::from_str(&format!("Failed to resize the image: {err}")))?;
+
+    Ok(buf)
+}
+
+/// Resize an already decoded image, preserving the aspect ratio.
+pub(crate) fn apply(image: &DynamicImage, width: u32, height: u32) -> DynamicImage {
+    image.resize(width, height, FilterType::Nearest)
+}
+
+/// Resize an already decoded image with the given quality, preserving the aspect ratio.
+pub(crate) fn apply_with(
+    image: &DynamicImage,
+    width: u32,
+    height: u32,
+    quality: ResizeQuality,
+) -> DynamicImage {
+    quality.resample(image, |image, filter| image.resize(width, height, filter))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::utils::pixels::tests::{assert_no_dark_fringe, transparent_sample};
 
     #[test]
     fn test_resize() {
@@ -43,4 +83,29 @@ mod test {
             .save("test-output/aspect_resized.jpg")
             .unwrap();
     }
+
+    #[test]
+    fn test_resize_in_linear_light() {
+        // Single pixel white lines on black, which average to mid gray in sRGB.
+        let lines = DynamicImage::ImageLuma8(image::GrayImage::from_fn(64, 64, |x, _| {
+            image::Luma([if x % 2 == 0 { 255 } else { 0 }])
+        }));
+
+        let gamma = lines.resize(16, 16, FilterType::Triangle).to_luma8();
+        let linear = apply_with(&lines, 16, 16, ResizeQuality::Balanced).to_luma8();
+
+        // Half the light is 0.5 linear, which is 188 in sRGB.
+        assert!(gamma.get_pixel(8, 8)[0].abs_diff(128) <= 2);
+        assert!(linear.get_pixel(8, 8)[0].abs_diff(188) <= 2);
+        assert_eq!(linear.dimensions(), (16, 16));
+    }
+
+    #[test]
+    fn test_resize_transparent_edges() {
+        let logo = transparent_sample();
+
+        for quality in [ResizeQuality::Balanced, ResizeQuality::Best] {
+            assert_no_dark_fringe(&apply_with(&logo, 24, 24, quality));
+        }
+    }
 }
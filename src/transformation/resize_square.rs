@@ -1,8 +1,9 @@
 use std::io::Cursor;
 
-use image::imageops::FilterType;
+use image::{imageops::FilterType, DynamicImage};
 use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
 
+use super::quality::ResizeQuality;
 use crate::utils::read_image::read_image;
 
 /// Resize an image by the given dimension.
@@ -20,14 +21,25 @@ pub fn resize_square(image_data: Vec<u8>, side: u32) -> Result<Vec<u8>, JsValue>
         .map_err(|err| JsValue::from_str(&format!("Failed to read image.: {err}")))?;
 
     let mut buf = Vec::new();
-    image
-        .resize_exact(side, side, FilterType::Nearest)
+    apply(&image, side)
         .write_to(&mut Cursor::new(&mut buf), format)
         .map_err(|err| JsValue::from_str(&format!("Failed to resize image.: {err}")))?;
 
     Ok(buf)
 }
 
+/// Resize an already decoded image to a square.
+pub(crate) fn apply(image: &DynamicImage, side: u32) -> DynamicImage {
+    image.resize_exact(side, side, FilterType::Nearest)
+}
+
+/// Resize an already decoded image to a square with the given quality.
+pub(crate) fn apply_with(image: &DynamicImage, side: u32, quality: ResizeQuality) -> DynamicImage {
+    quality.resample(image, |image, filter| {
+        image.resize_exact(side, side, filter)
+    })
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
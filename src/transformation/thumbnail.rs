@@ -1,7 +1,10 @@
 use std::io::Cursor;
 
+use image::DynamicImage;
 use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
 
+use super::quality::ResizeQuality;
+use crate::utils::pixels::premultiplied;
 use crate::utils::read_image::read_image;
 
 /// Scale this image down to fit within a specific size.
@@ -21,14 +24,32 @@ pub fn thumbnail(image_data: Vec<u8>, width: u32, height: u32) -> Result<Vec<u8>
         .map_err(|err| JsValue::from_str(&format!("Failed to read image: {err}")))?;
 
     let mut buf = Vec::new();
-    image
-        .thumbnail(width, height)
+    apply(&image, width, height)
         .write_to(&mut Cursor::new(&mut buf), format)
         .map_err(|err| JsValue::from_str(&format!("Failed to create the thumbnail: {err}")))?;
 
     Ok(buf)
 }
 
+/// Scale an already decoded image down to fit within the bounds.
+pub(crate) fn apply(image: &DynamicImage, width: u32, height: u32) -> DynamicImage {
+    premultiplied(image, |image| image.thumbnail(width, height))
+}
+
+/// Thumbnail an already decoded image, in linear light for the better qualities.
+/// The thumbnail algorithm is the same for every quality.
+pub(crate) fn apply_with(
+    image: &DynamicImage,
+    width: u32,
+    height: u32,
+    quality: ResizeQuality,
+) -> DynamicImage {
+    match quality {
+        ResizeQuality::Fast => apply(image, width, height),
+        _ => quality.resample(image, |image, _| image.thumbnail(width, height)),
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
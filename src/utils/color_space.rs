@@ -0,0 +1,154 @@
+//! Conversions between sRGB, linear light and the OKLab / OKLCH perceptual spaces.
+//! All values are `f32` with sRGB and linear channels in `0.0..=1.0`.
+
+use image::{ColorType, DynamicImage};
+
+use super::pixels::convert;
+
+/// Decode an sRGB channel value to linear light.
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.040_45 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encode a linear light channel value as sRGB.
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Linear RGB to OKLab `[lightness, a, b]`, from Björn Ottosson's reference.
+pub fn linear_to_oklab([r, g, b]: [f32; 3]) -> [f32; 3] {
+    let l = (0.412_221_46 * r + 0.536_332_55 * g + 0.051_445_995 * b).cbrt();
+    let m = (0.211_903_5 * r + 0.680_699_5 * g + 0.107_396_96 * b).cbrt();
+    let s = (0.088_302_46 * r + 0.281_718_85 * g + 0.629_978_7 * b).cbrt();
+
+    [
+        0.210_454_26 * l + 0.793_617_8 * m - 0.004_072_047 * s,
+        1.977_998_5 * l - 2.428_592_2 * m + 0.450_593_7 * s,
+        0.025_904_037 * l + 0.782_771_77 * m - 0.808_675_77 * s,
+    ]
+}
+
+/// OKLab `[lightness, a, b]` back to linear RGB, which may fall outside `0.0..=1.0`.
+pub fn oklab_to_linear([lightness, a, b]: [f32; 3]) -> [f32; 3] {
+    let l = (lightness + 0.396_337_78 * a + 0.215_803_76 * b).powi(3);
+    let m = (lightness - 0.105_561_346 * a - 0.063_854_17 * b).powi(3);
+    let s = (lightness - 0.089_484_18 * a - 1.291_485_5 * b).powi(3);
+
+    [
+        4.076_741_7 * l - 3.307_711_6 * m + 0.230_969_94 * s,
+        -1.268_438 * l + 2.609_757_4 * m - 0.341_319_38 * s,
+        -0.004_196_086_3 * l - 0.703_418_6 * m + 1.707_614_7 * s,
+    ]
+}
+
+/// sRGB to OKLCH `[lightness, chroma, hue]`, with the hue in degrees `0.0..360.0`.
+pub fn srgb_to_oklch(rgb: [f32; 3]) -> [f32; 3] {
+    let [lightness, a, b] = linear_to_oklab(rgb.map(srgb_to_linear));
+    let hue = b.atan2(a).to_degrees().rem_euclid(360.0);
+    [lightness, a.hypot(b), hue]
+}
+
+/// OKLCH `[lightness, chroma, hue]` back to sRGB, clamped to `0.0..=1.0`.
+pub fn oklch_to_srgb([lightness, chroma, hue]: [f32; 3]) -> [f32; 3] {
+    let (sin, cos) = hue.to_radians().sin_cos();
+    oklab_to_linear([lightness, chroma * cos, chroma * sin])
+        .map(|value| linear_to_srgb(value.clamp(0.0, 1.0)))
+}
+
+/// Run `process` on a linear light copy of the image and encode the result
+/// back to the color type of `image`.
+/// Averaging linear light keeps fine detail and high contrast edges as bright
+/// as the eye sees them, while averaging sRGB values darkens them.
+/// Alpha is already linear and stays untouched. Float values outside
+/// `0.0..=1.0` are clipped, like in other color adjustments.
+pub fn in_linear_light(
+    image: &DynamicImage,
+    process: impl FnOnce(&DynamicImage) -> DynamicImage,
+) -> DynamicImage {
+    let color = image.color();
+    let processed = process(&to_linear(image));
+    from_linear(processed, color)
+}
+
+/// A linear light `Rgb32F` or `Rgba32F` copy of an sRGB image.
+fn to_linear(image: &DynamicImage) -> DynamicImage {
+    let mut linear = if image.color().has_alpha() {
+        DynamicImage::ImageRgba32F(image.to_rgba32f())
+    } else {
+        DynamicImage::ImageRgb32F(image.to_rgb32f())
+    };
+    map_color(&mut linear, srgb_to_linear);
+    linear
+}
+
+/// Encode a linear light image from `to_linear` as sRGB in the given color type.
+fn from_linear(mut linear: DynamicImage, color: ColorType) -> DynamicImage {
+    map_color(&mut linear, |value| linear_to_srgb(value.clamp(0.0, 1.0)));
+    convert(linear, color)
+}
+
+fn map_color(image: &mut DynamicImage, map: impl Fn(f32) -> f32) {
+    match image {
+        DynamicImage::ImageRgb32F(buffer) => {
+            buffer.iter_mut().for_each(|value| *value = map(*value));
+        }
+        DynamicImage::ImageRgba32F(buffer) => {
+            for pixel in buffer.chunks_exact_mut(4) {
+                pixel[..3].iter_mut().for_each(|value| *value = map(*value));
+            }
+        }
+        _ => unreachable!("linear images are stored as f32"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_srgb_round_trip() {
+        for step in 0..=255 {
+            let value = step as f32 / 255.0;
+            assert!((linear_to_srgb(srgb_to_linear(value)) - value).abs() < 1e-5);
+        }
+        assert!((srgb_to_linear(0.5) - 0.214).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_linear_light_round_trip() {
+        let image = DynamicImage::ImageRgba8(image::RgbaImage::from_fn(16, 16, |x, y| {
+            image::Rgba([x as u8 * 16, y as u8 * 16, 200, 128])
+        }));
+        let same = in_linear_light(&image, |linear| linear.clone());
+
+        assert_eq!(same.color(), ColorType::Rgba8);
+        assert_eq!(same.to_rgba8(), image.to_rgba8());
+    }
+
+    #[test]
+    fn test_oklch() {
+        // Reference values for pure red from the OKLab post.
+        let [lightness, chroma, hue] = srgb_to_oklch([1.0, 0.0, 0.0]);
+        assert!((lightness - 0.628).abs() < 1e-3);
+        assert!((chroma - 0.2577).abs() < 1e-3);
+        assert!((hue - 29.23).abs() < 0.1);
+
+        let [lightness, chroma, _] = srgb_to_oklch([1.0, 1.0, 1.0]);
+        assert!((lightness - 1.0).abs() < 1e-4 && chroma < 1e-4);
+
+        for rgb in [[0.2, 0.5, 0.9], [0.9, 0.8, 0.1], [0.3, 0.3, 0.3]] {
+            let back = oklch_to_srgb(srgb_to_oklch(rgb));
+            for (a, b) in back.iter().zip(rgb) {
+                assert!((a - b).abs() < 1e-4, "{rgb:?} came back as {back:?}");
+            }
+        }
+    }
+}
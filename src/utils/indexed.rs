@@ -0,0 +1,172 @@
+//! Palette images and their indexed PNG encoding.
+
+use std::collections::HashMap;
+
+use image::{DynamicImage, RgbImage, RgbaImage};
+use png::{BitDepth, ColorType, Compression, Encoder, EncodingError};
+
+/// An image stored as a palette of up to 256 RGBA colors and one palette
+/// index per pixel, row by row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Indexed {
+    pub width: u32,
+    pub height: u32,
+    pub palette: Vec<[u8; 4]>,
+    pub indices: Vec<u8>,
+}
+
+impl Indexed {
+    /// Build an indexed image, moving the transparent palette colors to the
+    /// front so the PNG `tRNS` chunk stays short.
+    pub fn new(width: u32, height: u32, palette: Vec<[u8; 4]>, indices: Vec<u8>) -> Self {
+        let mut order: Vec<usize> = (0..palette.len()).collect();
+        order.sort_by_key(|&index| palette[index][3] == u8::MAX);
+
+        let mut remap = vec![0u8; palette.len()];
+        for (new, &old) in order.iter().enumerate() {
+            remap[old] = new as u8;
+        }
+
+        Self {
+            width,
+            height,
+            palette: order.iter().map(|&index| palette[index]).collect(),
+            indices: indices
+                .into_iter()
+                .map(|index| remap[index as usize])
+                .collect(),
+        }
+    }
+
+    /// The image with a palette of its own colors, if it has no more than `max_colors`.
+    /// Fully transparent pixels all share one palette entry.
+    pub fn exact(image: &DynamicImage, max_colors: usize) -> Option<Self> {
+        let rgba = image.to_rgba8();
+        let mut lookup: HashMap<[u8; 4], u8> = HashMap::new();
+        let mut palette = Vec::new();
+        let mut indices = Vec::with_capacity(rgba.len() / 4);
+
+        for pixel in rgba.pixels() {
+            let color = if pixel[3] == 0 { [0; 4] } else { pixel.0 };
+            let index = match lookup.get(&color) {
+                Some(&index) => index,
+                None if palette.len() < max_colors.min(256) => {
+                    let index = palette.len() as u8;
+                    palette.push(color);
+                    lookup.insert(color, index);
+                    index
+                }
+                None => return None,
+            };
+            indices.push(index);
+        }
+
+        Some(Self::new(rgba.width(), rgba.height(), palette, indices))
+    }
+
+    /// Whether any palette color is not fully opaque.
+    pub fn has_alpha(&self) -> bool {
+        self.palette.iter().any(|color| color[3] < u8::MAX)
+    }
+
+    /// Expand the palette back into pixels, RGBA when a color is transparent and RGB otherwise.
+    pub fn to_image(&self) -> DynamicImage {
+        let colors = self
+            .indices
+            .iter()
+            .map(|&index| self.palette[index as usize]);
+        if self.has_alpha() {
+            let data = colors.flatten().collect();
+            DynamicImage::ImageRgba8(RgbaImage::from_raw(self.width, self.height, data).unwrap())
+        } else {
+            let data = colors.flat_map(|[r, g, b, _]| [r, g, b]).collect();
+            DynamicImage::ImageRgb8(RgbImage::from_raw(self.width, self.height, data).unwrap())
+        }
+    }
+
+    /// Encode as an indexed PNG. Palettes of up to 2, 4 or 16 colors use 1, 2
+    /// or 4 bits per pixel, and transparent colors are listed in a `tRNS` chunk.
+    pub fn to_png(&self) -> Result<Vec<u8>, EncodingError> {
+        let bits = match self.palette.len() {
+            0..=2 => 1,
+            3..=4 => 2,
+            5..=16 => 4,
+            _ => 8,
+        };
+
+        let mut buf = Vec::new();
+        let mut encoder = Encoder::new(&mut buf, self.width, self.height);
+        encoder.set_color(ColorType::Indexed);
+        encoder.set_depth(match bits {
+            1 => BitDepth::One,
+            2 => BitDepth::Two,
+            4 => BitDepth::Four,
+            _ => BitDepth::Eight,
+        });
+        encoder.set_compression(Compression::High);
+        encoder.set_palette(
+            self.palette
+                .iter()
+                .flat_map(|&[r, g, b, _]| [r, g, b])
+                .collect::<Vec<u8>>(),
+        );
+        let transparent = self
+            .palette
+            .iter()
+            .take_while(|color| color[3] < u8::MAX)
+            .map(|color| color[3])
+            .collect::<Vec<u8>>();
+        if !transparent.is_empty() {
+            encoder.set_trns(transparent);
+        }
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.packed_rows(bits))?;
+        writer.finish()?;
+        Ok(buf)
+    }
+
+    /// The indices packed `bits` to a byte, each row starting on a new byte.
+    fn packed_rows(&self, bits: usize) -> Vec<u8> {
+        if bits == 8 {
+            return self.indices.clone();
+        }
+
+        let per_byte = 8 / bits;
+        let mut packed = Vec::new();
+        for row in self.indices.chunks(self.width.max(1) as usize) {
+            for group in row.chunks(per_byte) {
+                let mut byte = 0u8;
+                for (slot, &index) in group.iter().enumerate() {
+                    byte |= index << (8 - bits * (slot + 1));
+                }
+                packed.push(byte);
+            }
+        }
+        packed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_indexed_png_round_trip() {
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_fn(13, 5, |x, y| match (x + y) % 3 {
+            0 => image::Rgba([255, 0, 0, 255]),
+            1 => image::Rgba([0, 0, 255, 128]),
+            _ => image::Rgba([9, 9, 9, 0]),
+        }));
+
+        let indexed = Indexed::exact(&image, 256).unwrap();
+        assert_eq!(indexed.palette.len(), 3);
+        assert_eq!(indexed.palette[2], [255, 0, 0, 255]);
+        assert!(Indexed::exact(&image, 2).is_none());
+
+        let decoded = image::load_from_memory(&indexed.to_png().unwrap()).unwrap();
+        let expected = indexed.to_image();
+        assert_eq!(decoded.to_rgba8(), expected.to_rgba8());
+        assert_eq!(decoded.to_rgba8().get_pixel(2, 0).0, [0, 0, 0, 0]);
+    }
+}
@@ -1 +1,5 @@
 pub mod read_image;
+pub mod write_image;
+pub mod pixels;
+pub mod color_space;
+pub mod indexed;
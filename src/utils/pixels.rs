@@ -0,0 +1,397 @@
+use image::{ColorType, DynamicImage, ImageBuffer, Pixel, Primitive};
+use num_traits::NumCast;
+
+/// Weights of red, green and blue in the luminance, as used by `DynamicImage::grayscale`.
+pub const LUMA: [f32; 3] = [0.2126, 0.7152, 0.0722];
+
+/// Convert `image` to the given color type.
+/// Images that already have that color type are returned untouched.
+pub fn convert(image: DynamicImage, color: ColorType) -> DynamicImage {
+    if image.color() == color {
+        return image;
+    }
+
+    match color {
+        ColorType::L8 => DynamicImage::ImageLuma8(image.into_luma8()),
+        ColorType::La8 => DynamicImage::ImageLumaA8(image.into_luma_alpha8()),
+        ColorType::Rgb8 => DynamicImage::ImageRgb8(image.into_rgb8()),
+        ColorType::Rgba8 => DynamicImage::ImageRgba8(image.into_rgba8()),
+        ColorType::L16 => DynamicImage::ImageLuma16(image.into_luma16()),
+        ColorType::La16 => DynamicImage::ImageLumaA16(image.into_luma_alpha16()),
+        ColorType::Rgb16 => DynamicImage::ImageRgb16(image.into_rgb16()),
+        ColorType::Rgba16 => DynamicImage::ImageRgba16(image.into_rgba16()),
+        ColorType::Rgb32F => DynamicImage::ImageRgb32F(image.into_rgb32f()),
+        _ => DynamicImage::ImageRgba32F(image.into_rgba32f()),
+    }
+}
+
+/// The color type with the bit depth of `base` that can hold the channels
+/// of both `base` and `other`.
+pub fn common_color(base: ColorType, other: ColorType) -> ColorType {
+    let color = base.has_color() || other.has_color();
+    let alpha = base.has_alpha() || other.has_alpha();
+
+    match (base.bytes_per_pixel() / base.channel_count(), color, alpha) {
+        (1, false, false) => ColorType::L8,
+        (1, false, true) => ColorType::La8,
+        (1, true, false) => ColorType::Rgb8,
+        (1, true, true) => ColorType::Rgba8,
+        (2, false, false) => ColorType::L16,
+        (2, false, true) => ColorType::La16,
+        (2, true, false) => ColorType::Rgb16,
+        (2, true, true) => ColorType::Rgba16,
+        (_, _, false) => ColorType::Rgb32F,
+        (_, _, true) => ColorType::Rgba32F,
+    }
+}
+
+/// A gray image of `values` in `0.0..=1.0` with the bit depth of `color`.
+/// Float images get a gray `Rgb32F` image, as `DynamicImage::grayscale` gives them.
+pub fn gray_like(color: ColorType, width: u32, height: u32, values: Vec<f32>) -> DynamicImage {
+    match color.bytes_per_pixel() / color.channel_count() {
+        1 => {
+            let values = values.into_iter().map(from_unit).collect();
+            DynamicImage::ImageLuma8(ImageBuffer::from_raw(width, height, values).unwrap())
+        }
+        2 => {
+            let values = values.into_iter().map(from_unit).collect();
+            DynamicImage::ImageLuma16(ImageBuffer::from_raw(width, height, values).unwrap())
+        }
+        _ => {
+            let values = values
+                .into_iter()
+                .flat_map(|value| [value.clamp(0.0, 1.0); 3])
+                .collect();
+            DynamicImage::ImageRgb32F(ImageBuffer::from_raw(width, height, values).unwrap())
+        }
+    }
+}
+
+/// Map a channel value to `0.0..=1.0`.
+pub fn to_unit<S: Primitive>(value: S) -> f32 {
+    let max: f32 = NumCast::from(S::DEFAULT_MAX_VALUE).unwrap_or(1.0);
+    let value: f32 = NumCast::from(value).unwrap_or(0.0);
+    value / max
+}
+
+/// Map a value in `0.0..=1.0` back to a channel value, clamping and rounding as needed.
+/// Float images are clamped too: they are treated as display referred, so color
+/// adjustments clip values outside `0.0..=1.0`. Only operations that pick or average
+/// samples without mapping them, like the median, resizes and blurs, keep those values.
+pub fn from_unit<S: Primitive>(value: f32) -> S {
+    let max: f32 = NumCast::from(S::DEFAULT_MAX_VALUE).unwrap_or(1.0);
+    let scaled = value.clamp(0.0, 1.0) * max;
+    let scaled = if max > 1.0 { scaled.round() } else { scaled };
+    NumCast::from(scaled).unwrap_or(S::DEFAULT_MIN_VALUE)
+}
+
+/// Number of leading channels that hold color, leaving alpha untouched.
+pub fn color_channels<P: Pixel>() -> usize {
+    let channels = P::CHANNEL_COUNT as usize;
+    if P::HAS_ALPHA { channels - 1 } else { channels }
+}
+
+/// Run `$body` with `$buffer` bound to the image buffer inside a `DynamicImage`,
+/// whatever its color type.
+macro_rules! with_buffer {
+    ($image:expr, $buffer:ident => $body:expr) => {
+        match $image {
+            image::DynamicImage::ImageLuma8($buffer) => $body,
+            image::DynamicImage::ImageLumaA8($buffer) => $body,
+            image::DynamicImage::ImageRgb8($buffer) => $body,
+            image::DynamicImage::ImageRgba8($buffer) => $body,
+            image::DynamicImage::ImageLuma16($buffer) => $body,
+            image::DynamicImage::ImageLumaA16($buffer) => $body,
+            image::DynamicImage::ImageRgb16($buffer) => $body,
+            image::DynamicImage::ImageRgba16($buffer) => $body,
+            image::DynamicImage::ImageRgb32F($buffer) => $body,
+            image::DynamicImage::ImageRgba32F($buffer) => $body,
+            _ => unreachable!("unsupported color type"),
+        }
+    };
+}
+
+/// Like `with_buffer!`, for bodies that build a new buffer of the same
+/// color type. Evaluates to a `DynamicImage`.
+macro_rules! map_buffer {
+    ($image:expr, $buffer:ident => $body:expr) => {
+        match $image {
+            image::DynamicImage::ImageLuma8($buffer) => image::DynamicImage::ImageLuma8($body),
+            image::DynamicImage::ImageLumaA8($buffer) => image::DynamicImage::ImageLumaA8($body),
+            image::DynamicImage::ImageRgb8($buffer) => image::DynamicImage::ImageRgb8($body),
+            image::DynamicImage::ImageRgba8($buffer) => image::DynamicImage::ImageRgba8($body),
+            image::DynamicImage::ImageLuma16($buffer) => image::DynamicImage::ImageLuma16($body),
+            image::DynamicImage::ImageLumaA16($buffer) => image::DynamicImage::ImageLumaA16($body),
+            image::DynamicImage::ImageRgb16($buffer) => image::DynamicImage::ImageRgb16($body),
+            image::DynamicImage::ImageRgba16($buffer) => image::DynamicImage::ImageRgba16($body),
+            image::DynamicImage::ImageRgb32F($buffer) => image::DynamicImage::ImageRgb32F($body),
+            image::DynamicImage::ImageRgba32F($buffer) => image::DynamicImage::ImageRgba32F($body),
+            _ => unreachable!("unsupported color type"),
+        }
+    };
+}
+
+pub(crate) use {map_buffer, with_buffer};
+
+/// Run `map` on every pixel as `[r, g, b, a]` in `0.0..=1.0`, in place.
+/// Gray pixels are read with equal r, g and b and written back from r.
+/// Pixels without alpha read it as 1 and drop the alpha `map` returns.
+pub fn map_rgba(image: &mut DynamicImage, map: impl Fn([f32; 4]) -> [f32; 4]) {
+    with_buffer!(image, buffer => {
+        for pixel in buffer.pixels_mut() {
+            let channels = pixel.channels_mut();
+            let rgba = match *channels {
+                [l] => [to_unit(l), to_unit(l), to_unit(l), 1.0],
+                [l, a] => [to_unit(l), to_unit(l), to_unit(l), to_unit(a)],
+                [r, g, b] => [to_unit(r), to_unit(g), to_unit(b), 1.0],
+                [r, g, b, a] => [to_unit(r), to_unit(g), to_unit(b), to_unit(a)],
+                _ => unreachable!("pixels have one to four channels"),
+            };
+            let [r, g, b, a] = map(rgba);
+            match channels {
+                [l] => *l = from_unit(r),
+                [l, alpha] => (*l, *alpha) = (from_unit(r), from_unit(a)),
+                [cr, cg, cb] => (*cr, *cg, *cb) = (from_unit(r), from_unit(g), from_unit(b)),
+                [cr, cg, cb, alpha] => {
+                    (*cr, *cg, *cb, *alpha) = (from_unit(r), from_unit(g), from_unit(b), from_unit(a))
+                }
+                _ => unreachable!("pixels have one to four channels"),
+            }
+        }
+    });
+}
+
+/// Run `map` on every color channel value in `0.0..=1.0`, in place, leaving alpha untouched.
+/// `map` gets the channel, 0 for red or gray, 1 for green and 2 for blue, and the value.
+/// 8 and 16-bit images go through a lookup table per channel instead of calling `map` per pixel.
+pub fn map_channels(image: &mut DynamicImage, map: impl Fn(usize, f32) -> f32) {
+    with_buffer!(image, buffer => map_channel_buffer(buffer, &map));
+}
+
+fn map_channel_buffer<P: Pixel>(
+    buffer: &mut ImageBuffer<P, Vec<P::Subpixel>>,
+    map: &impl Fn(usize, f32) -> f32,
+) {
+    let color_channels = color_channels::<P>();
+    let channels = P::CHANNEL_COUNT as usize;
+
+    if size_of::<P::Subpixel>() > 2 {
+        for pixel in buffer.chunks_exact_mut(channels) {
+            for (channel, value) in pixel[..color_channels].iter_mut().enumerate() {
+                *value = from_unit(map(channel, to_unit(*value)));
+            }
+        }
+        return;
+    }
+
+    let levels: usize =
+        NumCast::from(P::Subpixel::DEFAULT_MAX_VALUE).map_or(256, |max: usize| max + 1);
+    let tables: Vec<Vec<P::Subpixel>> = (0..color_channels)
+        .map(|channel| {
+            (0..levels)
+                .map(|level| from_unit(map(channel, level as f32 / (levels - 1) as f32)))
+                .collect()
+        })
+        .collect();
+
+    for pixel in buffer.chunks_exact_mut(channels) {
+        for (value, table) in pixel.iter_mut().zip(&tables) {
+            let index: usize = NumCast::from(*value).unwrap_or(0);
+            *value = table[index];
+        }
+    }
+}
+
+/// Replace the luminance of every pixel, in place. `map` gets the pixel index
+/// and its luminance in `0.0..=1.0`, the same as `to_luma32f`, and returns the new one.
+/// The change is added to every color channel, keeping hue and alpha.
+pub fn map_luminance(image: &mut DynamicImage, map: impl Fn(usize, f32) -> f32) {
+    with_buffer!(image, buffer => map_luminance_buffer(buffer, &map));
+}
+
+fn map_luminance_buffer<P: Pixel>(
+    buffer: &mut ImageBuffer<P, Vec<P::Subpixel>>,
+    map: &impl Fn(usize, f32) -> f32,
+) {
+    let color_channels = color_channels::<P>();
+    let channels = P::CHANNEL_COUNT as usize;
+
+    for (index, pixel) in buffer.chunks_exact_mut(channels).enumerate() {
+        let color = &mut pixel[..color_channels];
+        let luminance = match color {
+            [l] => to_unit(*l),
+            [r, g, b] => LUMA[0] * to_unit(*r) + LUMA[1] * to_unit(*g) + LUMA[2] * to_unit(*b),
+            _ => unreachable!("pixels have one or three color channels"),
+        };
+        let shift = map(index, luminance) - luminance;
+        for value in color {
+            *value = from_unit(to_unit(*value) + shift);
+        }
+    }
+}
+
+/// Run `process` on a copy of the image with its color multiplied by alpha,
+/// then divide the color of the result by its alpha again.
+/// Filters that average pixels then ignore the color of transparent pixels,
+/// so the edges of transparent images do not pick up dark or colored fringes.
+/// Images without alpha are processed as they are.
+pub fn premultiplied(
+    image: &DynamicImage,
+    process: impl FnOnce(&DynamicImage) -> DynamicImage,
+) -> DynamicImage {
+    let color = image.color();
+    if !color.has_alpha() {
+        return process(image);
+    }
+
+    let mut rgba = image.to_rgba32f();
+    for pixel in rgba.pixels_mut() {
+        let alpha = pixel[3];
+        pixel.0[..3].iter_mut().for_each(|value| *value *= alpha);
+    }
+
+    // Float images keep values outside `0.0..=1.0`, integer ones are clipped to their range.
+    let float = color == ColorType::Rgba32F;
+    let mut rgba = process(&DynamicImage::ImageRgba32F(rgba)).into_rgba32f();
+    for pixel in rgba.pixels_mut() {
+        let alpha = pixel[3];
+        pixel.0[..3].iter_mut().for_each(|value| {
+            *value = match *value / alpha {
+                _ if alpha <= 0.0 => 0.0,
+                straight if float => straight,
+                straight => straight.clamp(0.0, 1.0),
+            }
+        });
+    }
+    convert(DynamicImage::ImageRgba32F(rgba), color)
+}
+
+/// Mix `filtered` into `original` pixel by pixel. `weight` gets the pixel
+/// index and returns 0 to keep the original, 1 to keep the filtered pixel.
+/// Both images are brought to a color type that holds the channels of either.
+pub fn blend(
+    original: &DynamicImage,
+    filtered: DynamicImage,
+    weight: impl Fn(usize) -> f32,
+) -> DynamicImage {
+    let color = common_color(original.color(), filtered.color());
+    let original = convert(original.clone(), color);
+    let mut filtered = convert(filtered, color);
+
+    match (&original, &mut filtered) {
+        (DynamicImage::ImageLuma8(a), DynamicImage::ImageLuma8(b)) => mix(a, b, weight),
+        (DynamicImage::ImageLumaA8(a), DynamicImage::ImageLumaA8(b)) => mix(a, b, weight),
+        (DynamicImage::ImageRgb8(a), DynamicImage::ImageRgb8(b)) => mix(a, b, weight),
+        (DynamicImage::ImageRgba8(a), DynamicImage::ImageRgba8(b)) => mix(a, b, weight),
+        (DynamicImage::ImageLuma16(a), DynamicImage::ImageLuma16(b)) => mix(a, b, weight),
+        (DynamicImage::ImageLumaA16(a), DynamicImage::ImageLumaA16(b)) => mix(a, b, weight),
+        (DynamicImage::ImageRgb16(a), DynamicImage::ImageRgb16(b)) => mix(a, b, weight),
+        (DynamicImage::ImageRgba16(a), DynamicImage::ImageRgba16(b)) => mix(a, b, weight),
+        (DynamicImage::ImageRgb32F(a), DynamicImage::ImageRgb32F(b)) => mix(a, b, weight),
+        (DynamicImage::ImageRgba32F(a), DynamicImage::ImageRgba32F(b)) => mix(a, b, weight),
+        _ => unreachable!("both images were converted to the same color type"),
+    }
+
+    filtered
+}
+
+fn mix<P: Pixel>(
+    original: &ImageBuffer<P, Vec<P::Subpixel>>,
+    filtered: &mut ImageBuffer<P, Vec<P::Subpixel>>,
+    weight: impl Fn(usize) -> f32,
+) {
+    let channels = P::CHANNEL_COUNT as usize;
+    let pixels = filtered
+        .chunks_exact_mut(channels)
+        .zip(original.chunks_exact(channels))
+        .enumerate();
+
+    for (index, (filtered, original)) in pixels {
+        match weight(index) {
+            weight if weight >= 1.0 => {}
+            weight if weight <= 0.0 => filtered.copy_from_slice(original),
+            weight => {
+                for (f, &o) in filtered.iter_mut().zip(original) {
+                    let (fv, ov) = (to_unit(*f), to_unit(o));
+                    *f = from_unit(ov + (fv - ov) * weight);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// `sample.png`: a white disc on fully transparent black, like an exported logo.
+    pub(crate) fn transparent_sample() -> DynamicImage {
+        image::load_from_memory(include_bytes!("../../sample.png")).unwrap()
+    }
+
+    /// Check that the visible pixels of the processed `transparent_sample`
+    /// stayed white and that some soft, partly transparent edge is left.
+    pub(crate) fn assert_no_dark_fringe(image: &DynamicImage) {
+        let mut edge = 0;
+        for pixel in image.to_rgba8().pixels().filter(|pixel| pixel[3] > 0) {
+            assert!(pixel[0] >= 250, "dark fringe: {pixel:?}");
+            edge += (pixel[3] < 255) as u32;
+        }
+        assert!(edge > 0);
+    }
+
+    #[test]
+    fn test_unit_round_trip() {
+        for value in [0u8, 1, 127, 128, 254, 255] {
+            assert_eq!(from_unit::<u8>(to_unit(value)), value);
+        }
+        for value in [0u16, 1, 32767, 65535] {
+            assert_eq!(from_unit::<u16>(to_unit(value)), value);
+        }
+        assert_eq!(from_unit::<f32>(0.25), 0.25);
+        assert_eq!(from_unit::<u8>(1.5), 255);
+    }
+
+    #[test]
+    fn test_premultiplied() {
+        // Red on the left, transparent black on the right.
+        let image = DynamicImage::ImageRgba8(image::RgbaImage::from_fn(2, 1, |x, _| {
+            if x == 0 {
+                image::Rgba([255, 0, 0, 255])
+            } else {
+                image::Rgba([0, 0, 0, 0])
+            }
+        }));
+
+        let same = premultiplied(&image, |image| image.clone());
+        assert_eq!(same.color(), ColorType::Rgba8);
+        assert_eq!(same.to_rgba8(), image.to_rgba8());
+
+        let averaged = premultiplied(&image, |image| {
+            image.resize_exact(1, 1, image::imageops::FilterType::Triangle)
+        });
+        assert_eq!(averaged.to_rgba8().get_pixel(0, 0).0, [255, 0, 0, 128]);
+
+        // Brighter than white stays brighter than white in float images.
+        let hdr = DynamicImage::ImageRgba32F(image::Rgba32FImage::from_pixel(
+            1,
+            1,
+            image::Rgba([4.0, 0.5, -0.25, 0.5]),
+        ));
+        let same = premultiplied(&hdr, |image| image.clone());
+        assert_eq!(same.to_rgba32f().get_pixel(0, 0).0, [4.0, 0.5, -0.25, 0.5]);
+    }
+
+    #[test]
+    fn test_common_color() {
+        assert_eq!(
+            common_color(ColorType::Rgb8, ColorType::L8),
+            ColorType::Rgb8
+        );
+        assert_eq!(
+            common_color(ColorType::L16, ColorType::Rgba8),
+            ColorType::Rgba16
+        );
+        assert_eq!(common_color(ColorType::L8, ColorType::La8), ColorType::La8);
+    }
+}
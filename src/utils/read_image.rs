@@ -7,7 +7,8 @@ use image::{DynamicImage, ImageError, ImageReader};
 ///
 /// The reason we are passing a Vec<u8> is because wasm_bindgen can pass it to JS.
 /// It cannot directly pass DynamicImage to JS.
-pub fn read_image(image_data: Vec<u8>) -> Result<DynamicImage, ImageError> {
+/// Borrowed bytes, like the contents of a `WasmBuffer`, work too and are not copied.
+pub fn read_image<T: AsRef<[u8]>>(image_data: T) -> Result<DynamicImage, ImageError> {
     let img = ImageReader::new(Cursor::new(image_data))
         .with_guessed_format()?
         .decode()?;
@@ -0,0 +1,47 @@
+use std::io::Cursor;
+
+use image::{DynamicImage, ImageError, ImageFormat};
+
+/// Encode a `DynamicImage` back into bytes of the given format.
+///
+/// Formats that cannot store every color type, like JPEG which has no alpha
+/// channel and no 16-bit support, get a compatible 8-bit copy of the image.
+pub fn write_image(image: &DynamicImage, format: ImageFormat) -> Result<Vec<u8>, ImageError> {
+    let mut buf = Vec::new();
+
+    match format {
+        ImageFormat::Jpeg
+            if !matches!(
+                image,
+                DynamicImage::ImageLuma8(_) | DynamicImage::ImageRgb8(_)
+            ) =>
+        {
+            let converted = if image.color().has_color() {
+                DynamicImage::ImageRgb8(image.to_rgb8())
+            } else {
+                DynamicImage::ImageLuma8(image.to_luma8())
+            };
+            converted.write_to(&mut Cursor::new(&mut buf), format)?;
+        }
+        _ => image.write_to(&mut Cursor::new(&mut buf), format)?,
+    }
+
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use super::*;
+
+    #[test]
+    fn test_write_rgba_as_jpeg() {
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_pixel(8, 8, Rgba([10, 20, 30, 128])));
+        let bytes = write_image(&image, ImageFormat::Jpeg).unwrap();
+
+        let decoded = image::load_from_memory(&bytes).unwrap();
+        assert_eq!(decoded.width(), 8);
+        assert!(!decoded.color().has_alpha());
+    }
+}